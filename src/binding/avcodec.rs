@@ -9743,6 +9743,8 @@ extern "C" {
     pub fn av_bsf_init(ctx: *mut AVBSFContext) -> ::std::os::raw::c_int;
 }
 pub const AVERROR_EOF: i32 = -541478725;
+pub const AVERROR_DEMUXER_NOT_FOUND: i32 = -1296385272;
+pub const AVERROR_EXIT: i32 = -1414092869;
 extern "C" {
     #[doc = " Submit a packet for filtering."]
     #[doc = ""]
@@ -0,0 +1,138 @@
+use std::ffi::CString;
+use std::ptr;
+
+use super::binding::avcodec::{AVERROR_EOF, EAGAIN, EINVAL};
+use super::binding::avformat::{self, AVBSFContext, AVCodecParameters, AVStream};
+use super::error::*;
+use super::packet::Packet;
+use super::stream::Stream;
+
+#[derive(Debug)]
+pub struct BitstreamFilter {
+    ctx: *mut AVBSFContext,
+    stream_index: i32,
+}
+
+impl BitstreamFilter {
+    pub fn new(filters: &str, stream: &Stream) -> Result<Self, FFmpegError> {
+        unsafe { Self::from_handle(filters, stream.handle()) }
+    }
+
+    pub(crate) unsafe fn from_handle(
+        filters: &str,
+        handle: *mut AVStream,
+    ) -> Result<Self, FFmpegError> {
+        let c_filters = CString::new(filters).unwrap();
+        let mut bsf = BitstreamFilter {
+            ctx: ptr::null_mut(),
+            stream_index: (*handle).index,
+        };
+
+        let ret = avformat::av_bsf_list_parse_str(c_filters.as_ptr(), &mut bsf.ctx);
+        if ret < 0 {
+            return Err(FFmpegError::new(ret, "bsf_parse"));
+        }
+
+        let ret = avformat::avcodec_parameters_copy((*bsf.ctx).par_in, (*handle).codecpar);
+        if ret < 0 {
+            return Err(FFmpegError::new(ret, "bsf_copy_parameters"));
+        }
+        (*bsf.ctx).time_base_in = (*handle).time_base;
+
+        let ret = avformat::av_bsf_init(bsf.ctx);
+        if ret < 0 {
+            return Err(FFmpegError::new(ret, "bsf_init"));
+        }
+        Ok(bsf)
+    }
+
+    pub fn stream_index(&self) -> i32 {
+        self.stream_index
+    }
+
+    pub fn time_base(&self) -> (i32, i32) {
+        let tb = unsafe { (*self.ctx).time_base_out };
+        (tb.num, tb.den)
+    }
+
+    pub(crate) fn parameters(&self) -> *mut AVCodecParameters {
+        unsafe { (*self.ctx).par_out }
+    }
+
+    pub fn extradata(&self) -> &[u8] {
+        unsafe {
+            let par = (*self.ctx).par_out;
+            if (*par).extradata.is_null() || (*par).extradata_size <= 0 {
+                return &[];
+            }
+            std::slice::from_raw_parts((*par).extradata, (*par).extradata_size as usize)
+        }
+    }
+
+    pub fn send(&mut self, mut packet: Packet) -> Result<(), FFmpegError> {
+        if packet.stream_index() != self.stream_index {
+            return Err(FFmpegError::new(-(EINVAL as i32), "bsf_stream_mismatch"));
+        }
+        let ret = unsafe { avformat::av_bsf_send_packet(self.ctx, packet.as_mut_ptr()) };
+        if ret < 0 {
+            return Err(FFmpegError::new(ret, "bsf_send_packet"));
+        }
+        Ok(())
+    }
+
+    pub fn send_eof(&mut self) -> Result<(), FFmpegError> {
+        let ret = unsafe { avformat::av_bsf_send_packet(self.ctx, ptr::null_mut()) };
+        if ret < 0 {
+            return Err(FFmpegError::new(ret, "bsf_send_packet"));
+        }
+        Ok(())
+    }
+
+    pub fn receive(&mut self) -> Result<Option<Packet>, FFmpegError> {
+        let mut packet = Packet::new();
+        let ret = unsafe { avformat::av_bsf_receive_packet(self.ctx, packet.as_mut_ptr()) };
+        if ret == -(EAGAIN as i32) {
+            return Ok(None);
+        }
+        if ret < 0 {
+            return Err(FFmpegError::new(ret, "bsf_receive_packet"));
+        }
+
+        unsafe {
+            (*packet.as_mut_ptr()).stream_index = self.stream_index;
+            packet.set_time_base((*self.ctx).time_base_out);
+        }
+        Ok(Some(packet))
+    }
+
+    pub fn filter(&mut self, packet: Packet) -> Result<Vec<Packet>, FFmpegError> {
+        self.send(packet)?;
+        self.drain()
+    }
+
+    pub fn flush(&mut self) -> Result<Vec<Packet>, FFmpegError> {
+        self.send_eof()?;
+        self.drain()
+    }
+
+    fn drain(&mut self) -> Result<Vec<Packet>, FFmpegError> {
+        let mut packets = Vec::new();
+        loop {
+            match self.receive() {
+                Ok(Some(packet)) => packets.push(packet),
+                Ok(None) => return Ok(packets),
+                Err(ref err) if err.code() == AVERROR_EOF => return Ok(packets),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl Drop for BitstreamFilter {
+    fn drop(&mut self) {
+        if !self.ctx.is_null() {
+            unsafe { avformat::av_bsf_free(&mut self.ctx) };
+        }
+        self.ctx = ptr::null_mut();
+    }
+}
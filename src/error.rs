@@ -1,11 +1,22 @@
 use std::error::Error;
 use std::fmt::{self, Display};
 
-use super::binding::avcodec::AVERROR_EOF;
+use super::binding::avcodec::{AVERROR_EOF, AVERROR_EXIT, EIO, ESPIPE};
 use super::wrapper::error_wrapper::*;
 
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ErrorKind {
+    Eof,
+    Io,
+    NotSeekable,
+    Cancelled,
+    TimedOut,
+    Other,
+}
+
 #[derive(Debug)]
 pub struct FFmpegError {
+    kind: ErrorKind,
     code: i32,
     desc: String,
     detail: String,
@@ -26,13 +37,65 @@ impl Error for FFmpegError {
 impl FFmpegError {
     pub fn new(code: i32, desc: &str) -> Self {
         let desc = desc.to_string();
+        let kind: ErrorKind;
         let detail: String;
         if code == AVERROR_EOF {
+            kind = ErrorKind::Eof;
             detail = "AV_EOF".to_string();
         } else {
             let err_str = err2str(code);
+            kind = ErrorKind::Other;
             detail = format!("{}: {}", desc, err_str);
         }
-        Self { code, desc, detail }
+        Self {
+            kind,
+            code,
+            desc,
+            detail,
+        }
+    }
+
+    pub fn from_io(desc: &str, msg: &str) -> Self {
+        Self {
+            kind: ErrorKind::Io,
+            code: -(EIO as i32),
+            desc: desc.to_string(),
+            detail: format!("{}: {}", desc, msg),
+        }
+    }
+
+    pub fn not_seekable(desc: &str) -> Self {
+        Self {
+            kind: ErrorKind::NotSeekable,
+            code: -(ESPIPE as i32),
+            desc: desc.to_string(),
+            detail: format!("{}: stream is not seekable", desc),
+        }
+    }
+
+    pub fn cancelled(desc: &str) -> Self {
+        Self {
+            kind: ErrorKind::Cancelled,
+            code: AVERROR_EXIT,
+            desc: desc.to_string(),
+            detail: format!("{}: operation cancelled", desc),
+        }
+    }
+
+    pub fn timed_out(desc: &str) -> Self {
+        Self {
+            kind: ErrorKind::TimedOut,
+            code: AVERROR_EXIT,
+            desc: desc.to_string(),
+            detail: format!("{}: operation timed out", desc),
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn code(&self) -> i32 {
+        self.code
     }
 }
@@ -1,62 +1,163 @@
-use std::ffi::CString;
-use std::os::raw::c_void;
+use std::any::Any;
+use std::ffi::{CStr, CString};
+use std::io::{Read, Seek};
+use std::marker::PhantomData;
+use std::os::raw::{c_char, c_void};
 use std::ptr;
+use std::sync::Arc;
 
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+
+use super::binding::avcodec::AVERROR_DEMUXER_NOT_FOUND;
 use super::binding::avformat::{
     self, AVFormatContext, AVIOContext, AVMediaType_AVMEDIA_TYPE_AUDIO,
     AVMediaType_AVMEDIA_TYPE_VIDEO, AVStream,
 };
 use super::error::*;
+use super::index::{IndexKey, MediaIndex};
+use super::options::OpenOptions;
+use super::packet::Packet;
 use super::stream::{self, Stream};
 use super::wrapper::format_wrapper::*;
 
 #[derive(Debug)]
-pub struct Format {
+pub struct Format<'a> {
     pub n_streams: i32,
     pub n_video_streams: i32,
     pub n_audio_streams: i32,
-    pub video_streams: Vec<Stream>,
-    pub audio_streams: Vec<Stream>,
+    pub video_streams: Vec<Stream<'a>>,
+    pub audio_streams: Vec<Stream<'a>>,
     pub fmtctx: *mut AVFormatContext,
     pub cache: *mut u8,
     pub ioctx: *mut AVIOContext,
     pub bd: *mut WrapperAvioBuffer,
+    pub reader: *mut WrapperReader,
+    pub interrupt: *mut WrapperInterrupt,
+    pub unused_options: Vec<String>,
+    loaded_index: Option<MediaIndex>,
+    _blob: Option<Box<dyn Any + Send>>,
+    _marker: PhantomData<&'a [u8]>,
 }
 
-pub fn load_video_from_file(path: &str) -> Result<Format, FFmpegError> {
-    let c_path = CString::new(path).unwrap();
-    let mut params = WrapperInputParams {
-        path: c_path.as_ptr(),
-        ns: 0,
-        ans: 0,
-        vns: 0,
-        fmtctx: ptr::null_mut(),
-        ioctx: ptr::null_mut(),
-        ioctx_buffer: ptr::null_mut(),
-        iobuffer: ptr::null_mut(),
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProbeResult {
+    pub name: String,
+    pub long_name: String,
+    pub mime_type: Option<String>,
+    pub score: i32,
+}
+
+pub(crate) enum Input<'a> {
+    File(&'a str),
+    Blob(*const u8, usize, Option<Box<dyn Any + Send>>),
+    Reader(WrapperReaderInner),
+}
+
+pub fn load_video_from_file(path: &str) -> Result<Format<'static>, FFmpegError> {
+    OpenOptions::new().open_file(path)
+}
+
+pub fn load_video_from_blob(blob: Vec<u8>) -> Result<Format<'static>, FFmpegError> {
+    OpenOptions::new().open_blob(blob)
+}
+
+pub fn load_video_from_shared(blob: Arc<[u8]>) -> Result<Format<'static>, FFmpegError> {
+    OpenOptions::new().open_shared(blob)
+}
+
+#[cfg(feature = "bytes")]
+pub fn load_video_from_bytes(blob: Bytes) -> Result<Format<'static>, FFmpegError> {
+    OpenOptions::new().open_bytes(blob)
+}
+
+pub fn load_video_from_slice(blob: &[u8]) -> Result<Format<'_>, FFmpegError> {
+    OpenOptions::new().open_slice(blob)
+}
+
+pub fn load_video_from_reader<R>(reader: R) -> Result<Format<'static>, FFmpegError>
+where
+    R: Read + Seek + Send + 'static,
+{
+    OpenOptions::new().open_reader(reader)
+}
+
+pub fn load_video_from_stream<R>(reader: R) -> Result<Format<'static>, FFmpegError>
+where
+    R: Read + Send + 'static,
+{
+    OpenOptions::new().open_stream(reader)
+}
+
+pub fn probe(bytes: &[u8]) -> Option<ProbeResult> {
+    let mut score = 0;
+    let fmt = unsafe { wrapper_probe_input(bytes, &mut score) };
+    if fmt.is_null() {
+        return None;
+    }
+
+    let to_string = |s: *const c_char| unsafe {
+        if s.is_null() {
+            None
+        } else {
+            Some(CStr::from_ptr(s).to_string_lossy().into_owned())
+        }
     };
+    unsafe {
+        Some(ProbeResult {
+            name: to_string((*fmt).name).unwrap_or_default(),
+            long_name: to_string((*fmt).long_name).unwrap_or_default(),
+            mime_type: to_string((*fmt).mime_type),
+            score,
+        })
+    }
+}
+
+pub(crate) unsafe fn open_input<'a>(
+    input: Input,
+    options: &OpenOptions,
+) -> Result<Format<'a>, FFmpegError> {
+    let mut input_format = ptr::null_mut();
+    if let Some(ref name) = options.input_format {
+        let c_name = CString::new(name.as_str()).unwrap();
+        input_format = avformat::av_find_input_format(c_name.as_ptr());
+        if input_format.is_null() {
+            return Err(FFmpegError::new(AVERROR_DEMUXER_NOT_FOUND, "ffmpeg_open"));
+        }
+    }
+
+    let mut c_path = None;
+    let mut buffer = ptr::null_mut();
+    let mut reader = ptr::null_mut();
+    let mut blob = None;
+    match input {
+        Input::File(path) => c_path = Some(CString::new(path).unwrap()),
+        Input::Blob(data, size, owner) => {
+            buffer = wrapper_borrow_buffer(data, size as i64);
+            blob = owner;
+        }
+        Input::Reader(inner) => {
+            reader = Box::into_raw(Box::new(WrapperReader {
+                inner,
+                error: None,
+                interrupt: ptr::null_mut(),
+            }));
+        }
+    }
+    let path = c_path.as_ref().map_or(ptr::null(), |p| p.as_ptr());
+
+    let mut interrupt = ptr::null_mut();
+    let deadline = options.effective_deadline();
+    if options.cancel_token.is_some() || deadline.is_some() {
+        interrupt = Box::into_raw(Box::new(WrapperInterrupt {
+            cancelled: options.cancel_token.as_ref().map(|t| t.cancelled.clone()),
+            deadline,
+            reason: 0,
+        }));
+    }
 
-    let ret = unsafe { wrapper_avformat_open_input(c_path.as_ptr(), &mut params) };
-    match ret {
-        0 => Ok(Format {
-            n_streams: params.ns,
-            n_video_streams: params.vns,
-            n_audio_streams: params.ans,
-            video_streams: vec![],
-            audio_streams: vec![],
-            fmtctx: params.fmtctx,
-            ioctx: ptr::null_mut(),
-            cache: ptr::null_mut(),
-            bd: ptr::null_mut(),
-        }),
-        code => Err(FFmpegError::new(code, "ffmpeg_open")),
-    }
-}
-
-pub fn load_video_from_blob(blob: Vec<u8>) -> Result<Format, FFmpegError> {
-    let buffer = unsafe { wrapper_fillin_buffer(blob.as_ptr(), blob.len() as i64) };
     let mut params = WrapperInputParams {
-        path: ptr::null_mut(),
+        path,
         ns: 0,
         ans: 0,
         vns: 0,
@@ -64,26 +165,47 @@ pub fn load_video_from_blob(blob: Vec<u8>) -> Result<Format, FFmpegError> {
         ioctx: ptr::null_mut(),
         ioctx_buffer: ptr::null_mut(),
         iobuffer: buffer,
+        reader,
+        input_format,
+        interrupt,
+        fmt_opts: wrapper_dict_from_pairs(&options.format_options),
+        info_opts: wrapper_dict_from_pairs(&options.stream_info_options),
     };
 
-    let ret = unsafe { wrapper_avformat_open_input(ptr::null_mut(), &mut params) };
-    match ret {
-        0 => Ok(Format {
-            n_streams: params.ns,
-            n_video_streams: params.vns,
-            n_audio_streams: params.ans,
-            video_streams: vec![],
-            audio_streams: vec![],
-            fmtctx: params.fmtctx,
-            ioctx: params.ioctx,
-            cache: unsafe { (*buffer).base },
-            bd: buffer,
-        }),
-        code => Err(FFmpegError::new(code, "ffmpeg_open")),
+    let ret = wrapper_avformat_open_input(path, &mut params);
+    let mut unused_options = wrapper_dict_keys(params.fmt_opts);
+    unused_options.extend(wrapper_dict_keys(params.info_opts));
+    avformat::av_dict_free(&mut params.fmt_opts);
+    avformat::av_dict_free(&mut params.info_opts);
+
+    let mut format = Format {
+        n_streams: params.ns,
+        n_video_streams: params.vns,
+        n_audio_streams: params.ans,
+        video_streams: vec![],
+        audio_streams: vec![],
+        fmtctx: params.fmtctx,
+        ioctx: params.ioctx,
+        cache: ptr::null_mut(),
+        bd: buffer,
+        reader,
+        interrupt,
+        unused_options,
+        loaded_index: None,
+        _blob: blob,
+        _marker: PhantomData,
+    };
+    if ret != 0 {
+        return Err(format.error(ret, "ffmpeg_open"));
     }
+    format.loaded_index = options
+        .index
+        .clone()
+        .filter(|index| index.is_valid_for(&format));
+    Ok(format)
 }
 
-impl Drop for Format {
+impl<'a> Drop for Format<'a> {
     fn drop(&mut self) {
         if !self.fmtctx.is_null() {
             unsafe { avformat::avformat_close_input(&mut self.fmtctx) };
@@ -101,29 +223,110 @@ impl Drop for Format {
             unsafe { avformat::free(self.bd as *mut c_void) };
             self.bd = ptr::null_mut::<WrapperAvioBuffer>();
         }
+        if !self.reader.is_null() {
+            unsafe { drop(Box::from_raw(self.reader)) };
+            self.reader = ptr::null_mut::<WrapperReader>();
+        }
+        if !self.interrupt.is_null() {
+            unsafe { drop(Box::from_raw(self.interrupt)) };
+            self.interrupt = ptr::null_mut::<WrapperInterrupt>();
+        }
     }
 }
 
-impl Format {
-    fn get_streams(&mut self, t: i32, n: i32) -> Vec<Stream> {
+impl<'a> Format<'a> {
+    pub fn builder() -> OpenOptions {
+        OpenOptions::new()
+    }
+
+    pub fn is_seekable(&self) -> bool {
+        unsafe {
+            !self.fmtctx.is_null()
+                && !(*self.fmtctx).pb.is_null()
+                && (*(*self.fmtctx).pb).seekable != 0
+        }
+    }
+
+    pub(crate) fn interrupted(&self, desc: &str) -> Option<FFmpegError> {
+        if self.interrupt.is_null() {
+            return None;
+        }
+        match unsafe { (*self.interrupt).reason } {
+            WRAPPER_INTERRUPT_CANCELLED => Some(FFmpegError::cancelled(desc)),
+            WRAPPER_INTERRUPT_TIMED_OUT => Some(FFmpegError::timed_out(desc)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn reader_failed(&self, desc: &str) -> Option<FFmpegError> {
+        if self.reader.is_null() {
+            return None;
+        }
+        unsafe { &(*self.reader).error }
+            .as_ref()
+            .map(|msg| FFmpegError::from_io(desc, msg))
+    }
+
+    pub(crate) fn error(&self, code: i32, desc: &str) -> FFmpegError {
+        if let Some(err) = self.interrupted(desc) {
+            return err;
+        }
+        if let Some(err) = self.reader_failed(desc) {
+            return err;
+        }
+        FFmpegError::new(code, desc)
+    }
+
+    pub fn read_packet(&mut self) -> Result<Packet, FFmpegError> {
+        let mut packet = Packet::new();
+        let ret = unsafe { avformat::av_read_frame(self.fmtctx, packet.as_mut_ptr()) };
+        if ret < 0 {
+            return Err(self.error(ret, "read_packet"));
+        }
+
+        let time_base = unsafe {
+            let streams = std::slice::from_raw_parts(
+                (*self.fmtctx).streams,
+                (*self.fmtctx).nb_streams as usize,
+            );
+            (*streams[packet.stream_index() as usize]).time_base
+        };
+        packet.set_time_base(time_base);
+        Ok(packet)
+    }
+
+    pub fn packets(&mut self) -> Packets<'_, 'a> {
+        Packets {
+            format: self,
+            done: false,
+        }
+    }
+
+    fn get_streams(&mut self, t: i32, n: i32) -> Vec<Stream<'a>> {
         if n <= 0 {
             return vec![];
         }
 
         unsafe {
-            let mut streams: *mut *mut AVStream = std::mem::uninitialized();
+            let mut streams: *mut *mut AVStream = ptr::null_mut();
             wrapper_get_stream_handlers(self.fmtctx, t, n, &mut streams);
             let slice = std::slice::from_raw_parts(streams, n as usize);
             let mut ret = Vec::new();
             for stream in slice {
-                ret.push(stream::new_stream(*stream, self, t))
+                let mut stream = stream::new_stream(*stream, self, t);
+                if let Some(index) = self.loaded_index.as_ref() {
+                    if let Some(index) = index.stream(stream.index()) {
+                        stream.load_index(index.clone());
+                    }
+                }
+                ret.push(stream)
             }
             wrapper_free_stream_handlers(streams);
             ret
         }
     }
 
-    pub fn video_streams(&mut self) -> &mut Vec<Stream> {
+    pub fn video_streams(&mut self) -> &mut Vec<Stream<'a>> {
         if self.video_streams.is_empty() {
             self.video_streams =
                 self.get_streams(AVMediaType_AVMEDIA_TYPE_VIDEO, self.n_video_streams);
@@ -131,11 +334,54 @@ impl Format {
         &mut self.video_streams
     }
 
-    pub fn audio_streams(&mut self) -> &mut Vec<Stream> {
+    pub fn audio_streams(&mut self) -> &mut Vec<Stream<'a>> {
         if self.audio_streams.is_empty() {
             self.audio_streams =
                 self.get_streams(AVMediaType_AVMEDIA_TYPE_AUDIO, self.n_audio_streams);
         }
         &mut self.audio_streams
     }
+
+    pub fn index_loaded(&self) -> bool {
+        self.loaded_index.is_some()
+    }
+
+    // every video stream resumes decoding where it was, see Stream::build_index
+    pub fn build_index(&mut self) -> Result<MediaIndex, FFmpegError> {
+        let mut streams = vec![];
+        for stream in self.video_streams().iter_mut() {
+            let index = stream.build_index()?.clone();
+            streams.push((stream.index(), index));
+        }
+        Ok(MediaIndex {
+            key: unsafe { IndexKey::of(self) },
+            streams,
+        })
+    }
+}
+
+pub struct Packets<'f, 'a> {
+    format: &'f mut Format<'a>,
+    done: bool,
+}
+
+impl<'f, 'a> Iterator for Packets<'f, 'a> {
+    type Item = Result<Packet, FFmpegError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.format.read_packet() {
+            Ok(packet) => Some(Ok(packet)),
+            Err(ref err) if err.kind() == ErrorKind::Eof => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
 }
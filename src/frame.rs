@@ -0,0 +1,235 @@
+use super::binding::avformat::{
+    AVColorPrimaries, AVColorPrimaries_AVCOL_PRI_BT2020, AVColorPrimaries_AVCOL_PRI_BT470BG,
+    AVColorPrimaries_AVCOL_PRI_BT470M, AVColorPrimaries_AVCOL_PRI_BT709,
+    AVColorPrimaries_AVCOL_PRI_FILM, AVColorPrimaries_AVCOL_PRI_SMPTE170M,
+    AVColorPrimaries_AVCOL_PRI_SMPTE240M, AVColorPrimaries_AVCOL_PRI_SMPTE431,
+    AVColorPrimaries_AVCOL_PRI_SMPTE432, AVColorPrimaries_AVCOL_PRI_SMPTEST428_1,
+    AVColorPrimaries_AVCOL_PRI_UNSPECIFIED, AVColorRange, AVColorRange_AVCOL_RANGE_JPEG,
+    AVColorRange_AVCOL_RANGE_MPEG, AVColorRange_AVCOL_RANGE_UNSPECIFIED, AVColorSpace,
+    AVColorSpace_AVCOL_SPC_BT2020_CL, AVColorSpace_AVCOL_SPC_BT2020_NCL,
+    AVColorSpace_AVCOL_SPC_BT470BG, AVColorSpace_AVCOL_SPC_BT709, AVColorSpace_AVCOL_SPC_FCC,
+    AVColorSpace_AVCOL_SPC_RGB, AVColorSpace_AVCOL_SPC_SMPTE170M, AVColorSpace_AVCOL_SPC_SMPTE2085,
+    AVColorSpace_AVCOL_SPC_SMPTE240M, AVColorSpace_AVCOL_SPC_UNSPECIFIED,
+    AVColorSpace_AVCOL_SPC_YCOCG, AVColorTransferCharacteristic,
+    AVColorTransferCharacteristic_AVCOL_TRC_ARIB_STD_B67,
+    AVColorTransferCharacteristic_AVCOL_TRC_BT1361_ECG,
+    AVColorTransferCharacteristic_AVCOL_TRC_BT2020_10,
+    AVColorTransferCharacteristic_AVCOL_TRC_BT2020_12,
+    AVColorTransferCharacteristic_AVCOL_TRC_BT709, AVColorTransferCharacteristic_AVCOL_TRC_GAMMA22,
+    AVColorTransferCharacteristic_AVCOL_TRC_GAMMA28,
+    AVColorTransferCharacteristic_AVCOL_TRC_IEC61966_2_1,
+    AVColorTransferCharacteristic_AVCOL_TRC_IEC61966_2_4,
+    AVColorTransferCharacteristic_AVCOL_TRC_LINEAR, AVColorTransferCharacteristic_AVCOL_TRC_LOG,
+    AVColorTransferCharacteristic_AVCOL_TRC_LOG_SQRT,
+    AVColorTransferCharacteristic_AVCOL_TRC_SMPTE170M,
+    AVColorTransferCharacteristic_AVCOL_TRC_SMPTE240M,
+    AVColorTransferCharacteristic_AVCOL_TRC_SMPTEST2084,
+    AVColorTransferCharacteristic_AVCOL_TRC_SMPTEST428_1,
+    AVColorTransferCharacteristic_AVCOL_TRC_UNSPECIFIED, AVFrame, AVPictureType,
+    AVPictureType_AV_PICTURE_TYPE_B, AVPictureType_AV_PICTURE_TYPE_BI,
+    AVPictureType_AV_PICTURE_TYPE_I, AVPictureType_AV_PICTURE_TYPE_P,
+    AVPictureType_AV_PICTURE_TYPE_S, AVPictureType_AV_PICTURE_TYPE_SI,
+    AVPictureType_AV_PICTURE_TYPE_SP, AVRational,
+};
+use super::binding::avutil::AV_NOPTS_VALUE;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PictureType {
+    None,
+    I,
+    P,
+    B,
+    S,
+    Si,
+    Sp,
+    Bi,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ColorRange {
+    Unspecified,
+    Limited,
+    Full,
+    Other(u32),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ColorSpace {
+    Rgb,
+    Bt709,
+    Unspecified,
+    Fcc,
+    Bt470bg,
+    Smpte170m,
+    Smpte240m,
+    Ycocg,
+    Bt2020Ncl,
+    Bt2020Cl,
+    Smpte2085,
+    Other(u32),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ColorPrimaries {
+    Bt709,
+    Unspecified,
+    Bt470m,
+    Bt470bg,
+    Smpte170m,
+    Smpte240m,
+    Film,
+    Bt2020,
+    Smpte428,
+    Smpte431,
+    Smpte432,
+    Other(u32),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ColorTransfer {
+    Bt709,
+    Unspecified,
+    Gamma22,
+    Gamma28,
+    Smpte170m,
+    Smpte240m,
+    Linear,
+    Log,
+    LogSqrt,
+    Iec61966_2_4,
+    Bt1361Ecg,
+    Iec61966_2_1,
+    Bt2020_10,
+    Bt2020_12,
+    Smpte2084,
+    Smpte428,
+    AribStdB67,
+    Other(u32),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct FrameMeta {
+    pub key_frame: bool,
+    pub pict_type: PictureType,
+    pub pts: Option<i64>,
+    pub pkt_duration: i64,
+    pub time_base: (i32, i32),
+    pub coded_picture_number: i32,
+    pub display_picture_number: i32,
+    pub interlaced: bool,
+    pub top_field_first: bool,
+    pub repeat_pict: i32,
+    pub sample_aspect_ratio: (i32, i32),
+    pub color_range: ColorRange,
+    pub color_space: ColorSpace,
+    pub color_primaries: ColorPrimaries,
+    pub color_trc: ColorTransfer,
+}
+
+pub(crate) unsafe fn frame_meta(frame: *const AVFrame, time_base: AVRational) -> FrameMeta {
+    let f = &*frame;
+    FrameMeta {
+        key_frame: f.key_frame != 0,
+        pict_type: picture_type(f.pict_type),
+        pts: match f.best_effort_timestamp {
+            AV_NOPTS_VALUE => None,
+            pts => Some(pts),
+        },
+        pkt_duration: f.pkt_duration,
+        time_base: (time_base.num, time_base.den),
+        coded_picture_number: f.coded_picture_number,
+        display_picture_number: f.display_picture_number,
+        interlaced: f.interlaced_frame != 0,
+        top_field_first: f.top_field_first != 0,
+        repeat_pict: f.repeat_pict,
+        sample_aspect_ratio: (f.sample_aspect_ratio.num, f.sample_aspect_ratio.den),
+        color_range: color_range(f.color_range),
+        color_space: color_space(f.colorspace),
+        color_primaries: color_primaries(f.color_primaries),
+        color_trc: color_transfer(f.color_trc),
+    }
+}
+
+#[allow(non_upper_case_globals)]
+fn picture_type(t: AVPictureType) -> PictureType {
+    match t {
+        AVPictureType_AV_PICTURE_TYPE_I => PictureType::I,
+        AVPictureType_AV_PICTURE_TYPE_P => PictureType::P,
+        AVPictureType_AV_PICTURE_TYPE_B => PictureType::B,
+        AVPictureType_AV_PICTURE_TYPE_S => PictureType::S,
+        AVPictureType_AV_PICTURE_TYPE_SI => PictureType::Si,
+        AVPictureType_AV_PICTURE_TYPE_SP => PictureType::Sp,
+        AVPictureType_AV_PICTURE_TYPE_BI => PictureType::Bi,
+        _ => PictureType::None,
+    }
+}
+
+#[allow(non_upper_case_globals)]
+fn color_range(r: AVColorRange) -> ColorRange {
+    match r {
+        AVColorRange_AVCOL_RANGE_UNSPECIFIED => ColorRange::Unspecified,
+        AVColorRange_AVCOL_RANGE_MPEG => ColorRange::Limited,
+        AVColorRange_AVCOL_RANGE_JPEG => ColorRange::Full,
+        other => ColorRange::Other(other),
+    }
+}
+
+#[allow(non_upper_case_globals)]
+fn color_space(s: AVColorSpace) -> ColorSpace {
+    match s {
+        AVColorSpace_AVCOL_SPC_RGB => ColorSpace::Rgb,
+        AVColorSpace_AVCOL_SPC_BT709 => ColorSpace::Bt709,
+        AVColorSpace_AVCOL_SPC_UNSPECIFIED => ColorSpace::Unspecified,
+        AVColorSpace_AVCOL_SPC_FCC => ColorSpace::Fcc,
+        AVColorSpace_AVCOL_SPC_BT470BG => ColorSpace::Bt470bg,
+        AVColorSpace_AVCOL_SPC_SMPTE170M => ColorSpace::Smpte170m,
+        AVColorSpace_AVCOL_SPC_SMPTE240M => ColorSpace::Smpte240m,
+        AVColorSpace_AVCOL_SPC_YCOCG => ColorSpace::Ycocg,
+        AVColorSpace_AVCOL_SPC_BT2020_NCL => ColorSpace::Bt2020Ncl,
+        AVColorSpace_AVCOL_SPC_BT2020_CL => ColorSpace::Bt2020Cl,
+        AVColorSpace_AVCOL_SPC_SMPTE2085 => ColorSpace::Smpte2085,
+        other => ColorSpace::Other(other),
+    }
+}
+
+#[allow(non_upper_case_globals)]
+fn color_primaries(p: AVColorPrimaries) -> ColorPrimaries {
+    match p {
+        AVColorPrimaries_AVCOL_PRI_BT709 => ColorPrimaries::Bt709,
+        AVColorPrimaries_AVCOL_PRI_UNSPECIFIED => ColorPrimaries::Unspecified,
+        AVColorPrimaries_AVCOL_PRI_BT470M => ColorPrimaries::Bt470m,
+        AVColorPrimaries_AVCOL_PRI_BT470BG => ColorPrimaries::Bt470bg,
+        AVColorPrimaries_AVCOL_PRI_SMPTE170M => ColorPrimaries::Smpte170m,
+        AVColorPrimaries_AVCOL_PRI_SMPTE240M => ColorPrimaries::Smpte240m,
+        AVColorPrimaries_AVCOL_PRI_FILM => ColorPrimaries::Film,
+        AVColorPrimaries_AVCOL_PRI_BT2020 => ColorPrimaries::Bt2020,
+        AVColorPrimaries_AVCOL_PRI_SMPTEST428_1 => ColorPrimaries::Smpte428,
+        AVColorPrimaries_AVCOL_PRI_SMPTE431 => ColorPrimaries::Smpte431,
+        AVColorPrimaries_AVCOL_PRI_SMPTE432 => ColorPrimaries::Smpte432,
+        other => ColorPrimaries::Other(other),
+    }
+}
+
+#[allow(non_upper_case_globals)]
+fn color_transfer(t: AVColorTransferCharacteristic) -> ColorTransfer {
+    match t {
+        AVColorTransferCharacteristic_AVCOL_TRC_BT709 => ColorTransfer::Bt709,
+        AVColorTransferCharacteristic_AVCOL_TRC_UNSPECIFIED => ColorTransfer::Unspecified,
+        AVColorTransferCharacteristic_AVCOL_TRC_GAMMA22 => ColorTransfer::Gamma22,
+        AVColorTransferCharacteristic_AVCOL_TRC_GAMMA28 => ColorTransfer::Gamma28,
+        AVColorTransferCharacteristic_AVCOL_TRC_SMPTE170M => ColorTransfer::Smpte170m,
+        AVColorTransferCharacteristic_AVCOL_TRC_SMPTE240M => ColorTransfer::Smpte240m,
+        AVColorTransferCharacteristic_AVCOL_TRC_LINEAR => ColorTransfer::Linear,
+        AVColorTransferCharacteristic_AVCOL_TRC_LOG => ColorTransfer::Log,
+        AVColorTransferCharacteristic_AVCOL_TRC_LOG_SQRT => ColorTransfer::LogSqrt,
+        AVColorTransferCharacteristic_AVCOL_TRC_IEC61966_2_4 => ColorTransfer::Iec61966_2_4,
+        AVColorTransferCharacteristic_AVCOL_TRC_BT1361_ECG => ColorTransfer::Bt1361Ecg,
+        AVColorTransferCharacteristic_AVCOL_TRC_IEC61966_2_1 => ColorTransfer::Iec61966_2_1,
+        AVColorTransferCharacteristic_AVCOL_TRC_BT2020_10 => ColorTransfer::Bt2020_10,
+        AVColorTransferCharacteristic_AVCOL_TRC_BT2020_12 => ColorTransfer::Bt2020_12,
+        AVColorTransferCharacteristic_AVCOL_TRC_SMPTEST2084 => ColorTransfer::Smpte2084,
+        AVColorTransferCharacteristic_AVCOL_TRC_SMPTEST428_1 => ColorTransfer::Smpte428,
+        AVColorTransferCharacteristic_AVCOL_TRC_ARIB_STD_B67 => ColorTransfer::AribStdB67,
+        other => ColorTransfer::Other(other),
+    }
+}
@@ -0,0 +1,184 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::binding::avformat::{
+    self, AVCodecContext, AVStream, AVINDEX_DISCARD_FRAME, AVINDEX_KEYFRAME,
+};
+use super::binding::avutil::AV_NOPTS_VALUE;
+use super::error::*;
+use super::format::Format;
+use super::wrapper::stream_wrapper::wrapper_stream_seek_by_ts;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct IndexEntry {
+    pub pts: i64,
+    pub pos: Option<i64>,
+    pub key: bool,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FrameIndex {
+    pub time_base: (i32, i32),
+    pub entries: Vec<IndexEntry>,
+}
+
+impl FrameIndex {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn pts(&self, n: usize) -> Option<i64> {
+        self.entries.get(n).map(|e| e.pts)
+    }
+
+    pub fn time(&self, n: usize) -> Option<f64> {
+        let (num, den) = self.time_base;
+        self.pts(n).map(|pts| pts as f64 * num as f64 / den as f64)
+    }
+
+    pub fn frame_of_pts(&self, pts: i64) -> usize {
+        self.entries.partition_point(|e| e.pts < pts)
+    }
+
+    pub fn keyframe_before(&self, n: usize) -> Option<usize> {
+        let n = n.min(self.entries.len().checked_sub(1)?);
+        self.entries[..=n].iter().rposition(|e| e.key)
+    }
+}
+
+// identifies the input an index was built from, cheap enough to check on every open
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct IndexKey {
+    pub file_size: i64,
+    pub duration: i64,
+    pub n_streams: i32,
+}
+
+impl IndexKey {
+    pub(crate) unsafe fn of(format: *const Format) -> IndexKey {
+        let fmtctx = (*format).fmtctx;
+        let file_size = match (*fmtctx).pb {
+            pb if pb.is_null() => -1,
+            pb => avformat::avio_size(pb),
+        };
+        IndexKey {
+            file_size,
+            duration: (*fmtctx).duration,
+            n_streams: (*fmtctx).nb_streams as i32,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MediaIndex {
+    pub key: IndexKey,
+    pub streams: Vec<(i32, FrameIndex)>,
+}
+
+impl MediaIndex {
+    pub fn is_valid_for(&self, format: &Format) -> bool {
+        !format.fmtctx.is_null() && self.key == unsafe { IndexKey::of(format) }
+    }
+
+    pub fn stream(&self, index: i32) -> Option<&FrameIndex> {
+        self.streams
+            .iter()
+            .find(|(i, _)| *i == index)
+            .map(|(_, index)| index)
+    }
+}
+
+pub(crate) unsafe fn build_index(
+    format: *mut Format,
+    stream: *mut AVStream,
+    cctx: *mut AVCodecContext,
+) -> Result<FrameIndex, FFmpegError> {
+    let time_base = ((*stream).time_base.num, (*stream).time_base.den);
+    let mut entries = index_from_entries(stream);
+    if entries.is_empty() {
+        entries = index_from_packets(format, stream, cctx)?;
+    }
+    entries.sort_by_key(|e| e.pts);
+    entries.dedup_by_key(|e| e.pts);
+    Ok(FrameIndex { time_base, entries })
+}
+
+unsafe fn index_from_entries(stream: *mut AVStream) -> Vec<IndexEntry> {
+    // demuxer index timestamps are only presentation times without reordering
+    if (*stream).nb_index_entries <= 0 || (*(*stream).codecpar).video_delay != 0 {
+        return vec![];
+    }
+    let index =
+        std::slice::from_raw_parts((*stream).index_entries, (*stream).nb_index_entries as usize);
+    let entries: Vec<IndexEntry> = index
+        .iter()
+        .filter(|e| e.flags() as u32 & AVINDEX_DISCARD_FRAME == 0)
+        .map(|e| IndexEntry {
+            pts: e.timestamp,
+            pos: match e.pos {
+                -1 => None,
+                pos => Some(pos),
+            },
+            key: e.flags() as u32 & AVINDEX_KEYFRAME != 0,
+        })
+        .collect();
+
+    // many demuxers only index keyframes, and mov stores dts that a ctts offset moves away
+    // from the pts, so only trust an index that lists every frame starting at the stream start
+    let every_frame = entries.len() as i64 == (*stream).nb_frames;
+    let starts_at_pts = match (*stream).start_time {
+        AV_NOPTS_VALUE => false,
+        start => entries.iter().map(|e| e.pts).min() == Some(start),
+    };
+    if !every_frame || !starts_at_pts {
+        return vec![];
+    }
+    entries
+}
+
+unsafe fn index_from_packets(
+    format: *mut Format,
+    stream: *mut AVStream,
+    cctx: *mut AVCodecContext,
+) -> Result<Vec<IndexEntry>, FFmpegError> {
+    if !(*format).is_seekable() {
+        return Err(FFmpegError::not_seekable("ffmpeg_build_index"));
+    }
+    let start = match (*stream).start_time {
+        AV_NOPTS_VALUE => 0,
+        t => t,
+    };
+    let rewind = || {
+        let ret = wrapper_stream_seek_by_ts(stream, (*format).fmtctx, cctx, start);
+        match ret {
+            n if n < 0 => Err((*format).error(n, "ffmpeg_build_index")),
+            _ => Ok(()),
+        }
+    };
+
+    rewind()?;
+    let mut entries = vec![];
+    for packet in (*format).packets() {
+        let packet = packet?;
+        if packet.stream_index() != (*stream).index {
+            continue;
+        }
+        if let Some(pts) = packet.pts().or_else(|| packet.dts()) {
+            entries.push(IndexEntry {
+                pts,
+                pos: packet.pos(),
+                key: packet.is_key(),
+            });
+        }
+    }
+    rewind()?;
+    Ok(entries)
+}
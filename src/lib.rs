@@ -1,12 +1,27 @@
 mod binding;
 mod wrapper;
 
+pub mod bsf;
 pub mod error;
 pub mod format;
+pub mod frame;
+pub mod index;
+pub mod options;
+pub mod packet;
+pub mod remux;
 pub mod stream;
 
+pub use bsf::BitstreamFilter;
 pub use format::Format;
-pub use stream::{Frame, Orientation, Stream};
+pub use frame::{ColorPrimaries, ColorRange, ColorSpace, ColorTransfer, FrameMeta, PictureType};
+pub use index::{FrameIndex, IndexEntry, IndexKey, MediaIndex};
+pub use options::{CancelToken, OpenOptions};
+pub use packet::Packet;
+pub use remux::{remux, OutputSpec};
+pub use stream::{
+    Discard, Frame, FrameInfo, FrameRef, FramesAt, Keyframes, Orientation, PixelFormat, Plane,
+    Resize, Sample, SampleMode, Samples, ScaleAlgorithm, SeekMode, Stream, ThreadType, Threading,
+};
 
 use self::binding::avcodec;
 use self::binding::avformat;
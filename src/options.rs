@@ -0,0 +1,173 @@
+use std::io::{Read, Seek};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+
+use super::error::*;
+use super::format::{self, Format, Input};
+use super::index::MediaIndex;
+use super::wrapper::format_wrapper::WrapperReaderInner;
+
+#[derive(Debug, Default, Clone)]
+pub struct CancelToken {
+    pub(crate) cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct OpenOptions {
+    pub(crate) input_format: Option<String>,
+    pub(crate) cancel_token: Option<CancelToken>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) deadline: Option<Instant>,
+    pub(crate) format_options: Vec<(String, String)>,
+    pub(crate) stream_info_options: Vec<(String, String)>,
+    pub(crate) index: Option<MediaIndex>,
+}
+
+impl OpenOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn option(&mut self, key: &str, value: &str) -> &mut Self {
+        self.format_options
+            .push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn stream_info_option(&mut self, key: &str, value: &str) -> &mut Self {
+        self.stream_info_options
+            .push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn input_format(&mut self, name: &str) -> &mut Self {
+        self.input_format = Some(name.to_string());
+        self
+    }
+
+    pub fn video_size(&mut self, width: i32, height: i32) -> &mut Self {
+        self.option("video_size", &format!("{}x{}", width, height))
+    }
+
+    pub fn pixel_format(&mut self, name: &str) -> &mut Self {
+        self.option("pixel_format", name)
+    }
+
+    pub fn framerate(&mut self, fps: f32) -> &mut Self {
+        self.option("framerate", &fps.to_string())
+    }
+
+    pub fn sample_rate(&mut self, sample_rate: i32) -> &mut Self {
+        self.option("sample_rate", &sample_rate.to_string())
+    }
+
+    pub fn channels(&mut self, channels: i32) -> &mut Self {
+        self.option("channels", &channels.to_string())
+    }
+
+    pub fn cancel_token(&mut self, token: &CancelToken) -> &mut Self {
+        self.cancel_token = Some(token.clone());
+        self
+    }
+
+    pub fn index(&mut self, index: &MediaIndex) -> &mut Self {
+        self.index = Some(index.clone());
+        self
+    }
+
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn deadline(&mut self, deadline: Instant) -> &mut Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub(crate) fn effective_deadline(&self) -> Option<Instant> {
+        let timeout = self.timeout.map(|t| Instant::now() + t);
+        match (timeout, self.deadline) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    pub fn probesize(&mut self, bytes: i64) -> &mut Self {
+        self.option("probesize", &bytes.to_string())
+    }
+
+    pub fn analyzeduration(&mut self, duration: Duration) -> &mut Self {
+        self.option("analyzeduration", &duration.as_micros().to_string())
+    }
+
+    pub fn fpsprobesize(&mut self, frames: i32) -> &mut Self {
+        self.option("fpsprobesize", &frames.to_string())
+    }
+
+    pub fn fflags(&mut self, flags: &str) -> &mut Self {
+        self.option("fflags", flags)
+    }
+
+    pub fn ignore_editlist(&mut self, ignore: bool) -> &mut Self {
+        self.option("ignore_editlist", if ignore { "1" } else { "0" })
+    }
+
+    pub fn open_file(&self, path: &str) -> Result<Format<'static>, FFmpegError> {
+        unsafe { format::open_input(Input::File(path), self) }
+    }
+
+    pub fn open_blob(&self, blob: Vec<u8>) -> Result<Format<'static>, FFmpegError> {
+        let (data, size) = (blob.as_ptr(), blob.len());
+        unsafe { format::open_input(Input::Blob(data, size, Some(Box::new(blob))), self) }
+    }
+
+    pub fn open_shared(&self, blob: Arc<[u8]>) -> Result<Format<'static>, FFmpegError> {
+        let (data, size) = (blob.as_ptr(), blob.len());
+        unsafe { format::open_input(Input::Blob(data, size, Some(Box::new(blob))), self) }
+    }
+
+    #[cfg(feature = "bytes")]
+    pub fn open_bytes(&self, blob: Bytes) -> Result<Format<'static>, FFmpegError> {
+        let (data, size) = (blob.as_ptr(), blob.len());
+        unsafe { format::open_input(Input::Blob(data, size, Some(Box::new(blob))), self) }
+    }
+
+    pub fn open_slice<'a>(&self, blob: &'a [u8]) -> Result<Format<'a>, FFmpegError> {
+        unsafe { format::open_input(Input::Blob(blob.as_ptr(), blob.len(), None), self) }
+    }
+
+    pub fn open_reader<R>(&self, reader: R) -> Result<Format<'static>, FFmpegError>
+    where
+        R: Read + Seek + Send + 'static,
+    {
+        let inner = WrapperReaderInner::Seekable(Box::new(reader));
+        unsafe { format::open_input(Input::Reader(inner), self) }
+    }
+
+    pub fn open_stream<R>(&self, reader: R) -> Result<Format<'static>, FFmpegError>
+    where
+        R: Read + Send + 'static,
+    {
+        let inner = WrapperReaderInner::Forward(Box::new(reader));
+        unsafe { format::open_input(Input::Reader(inner), self) }
+    }
+}
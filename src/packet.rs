@@ -0,0 +1,171 @@
+use std::ptr;
+
+use super::binding::avformat::{
+    self, AVPacket, AVPacketSideDataType_AV_PKT_DATA_DISPLAYMATRIX,
+    AVPacketSideDataType_AV_PKT_DATA_MASTERING_DISPLAY_METADATA,
+    AVPacketSideDataType_AV_PKT_DATA_NEW_EXTRADATA, AVPacketSideDataType_AV_PKT_DATA_PALETTE,
+    AVPacketSideDataType_AV_PKT_DATA_PARAM_CHANGE, AVPacketSideDataType_AV_PKT_DATA_REPLAYGAIN,
+    AVPacketSideDataType_AV_PKT_DATA_SKIP_SAMPLES, AVPacketSideDataType_AV_PKT_DATA_STEREO3D,
+    AVRational, AV_PKT_FLAG_CORRUPT, AV_PKT_FLAG_DISCARD, AV_PKT_FLAG_KEY,
+};
+use super::binding::avutil::AV_NOPTS_VALUE;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SideDataKind {
+    Palette,
+    NewExtradata,
+    ParamChange,
+    ReplayGain,
+    DisplayMatrix,
+    Stereo3d,
+    SkipSamples,
+    MasteringDisplayMetadata,
+    Other(u32),
+}
+
+#[derive(Debug)]
+pub struct SideData<'p> {
+    pub kind: SideDataKind,
+    pub data: &'p [u8],
+}
+
+#[derive(Debug)]
+pub struct Packet {
+    handle: *mut AVPacket,
+    time_base: AVRational,
+}
+
+impl Packet {
+    pub(crate) fn new() -> Self {
+        Packet {
+            handle: unsafe { avformat::av_packet_alloc() },
+            time_base: AVRational { num: 0, den: 1 },
+        }
+    }
+
+    pub(crate) fn as_mut_ptr(&mut self) -> *mut AVPacket {
+        self.handle
+    }
+
+    pub(crate) fn raw_time_base(&self) -> AVRational {
+        self.time_base
+    }
+
+    pub(crate) fn set_time_base(&mut self, time_base: AVRational) {
+        self.time_base = time_base;
+    }
+
+    pub fn stream_index(&self) -> i32 {
+        unsafe { (*self.handle).stream_index }
+    }
+
+    pub fn pts(&self) -> Option<i64> {
+        match unsafe { (*self.handle).pts } {
+            AV_NOPTS_VALUE => None,
+            pts => Some(pts),
+        }
+    }
+
+    pub fn dts(&self) -> Option<i64> {
+        match unsafe { (*self.handle).dts } {
+            AV_NOPTS_VALUE => None,
+            dts => Some(dts),
+        }
+    }
+
+    pub fn duration(&self) -> i64 {
+        unsafe { (*self.handle).duration }
+    }
+
+    pub fn pos(&self) -> Option<i64> {
+        match unsafe { (*self.handle).pos } {
+            -1 => None,
+            pos => Some(pos),
+        }
+    }
+
+    pub fn time_base(&self) -> (i32, i32) {
+        (self.time_base.num, self.time_base.den)
+    }
+
+    pub fn is_key(&self) -> bool {
+        self.has_flag(AV_PKT_FLAG_KEY)
+    }
+
+    pub fn is_corrupt(&self) -> bool {
+        self.has_flag(AV_PKT_FLAG_CORRUPT)
+    }
+
+    pub fn is_discard(&self) -> bool {
+        self.has_flag(AV_PKT_FLAG_DISCARD)
+    }
+
+    pub fn data(&self) -> &[u8] {
+        unsafe {
+            let pkt = &*self.handle;
+            if pkt.data.is_null() || pkt.size <= 0 {
+                return &[];
+            }
+            std::slice::from_raw_parts(pkt.data, pkt.size as usize)
+        }
+    }
+
+    pub fn side_data(&self) -> Vec<SideData<'_>> {
+        unsafe {
+            let pkt = &*self.handle;
+            if pkt.side_data.is_null() || pkt.side_data_elems <= 0 {
+                return vec![];
+            }
+            std::slice::from_raw_parts(pkt.side_data, pkt.side_data_elems as usize)
+                .iter()
+                .map(|sd| SideData {
+                    kind: side_data_kind(sd.type_),
+                    data: if sd.data.is_null() || sd.size <= 0 {
+                        &[]
+                    } else {
+                        std::slice::from_raw_parts(sd.data, sd.size as usize)
+                    },
+                })
+                .collect()
+        }
+    }
+
+    fn has_flag(&self, flag: u32) -> bool {
+        unsafe { (*self.handle).flags & flag as i32 != 0 }
+    }
+}
+
+impl Clone for Packet {
+    fn clone(&self) -> Self {
+        let mut packet = Packet::new();
+        unsafe { avformat::av_packet_ref(packet.handle, self.handle) };
+        packet.time_base = self.time_base;
+        packet
+    }
+}
+
+impl Drop for Packet {
+    fn drop(&mut self) {
+        if !self.handle.is_null() {
+            unsafe { avformat::av_packet_free(&mut self.handle) };
+        }
+        self.handle = ptr::null_mut();
+    }
+}
+
+#[allow(non_upper_case_globals)]
+fn side_data_kind(t: u32) -> SideDataKind {
+    match t {
+        AVPacketSideDataType_AV_PKT_DATA_PALETTE => SideDataKind::Palette,
+        AVPacketSideDataType_AV_PKT_DATA_NEW_EXTRADATA => SideDataKind::NewExtradata,
+        AVPacketSideDataType_AV_PKT_DATA_PARAM_CHANGE => SideDataKind::ParamChange,
+        AVPacketSideDataType_AV_PKT_DATA_REPLAYGAIN => SideDataKind::ReplayGain,
+        AVPacketSideDataType_AV_PKT_DATA_DISPLAYMATRIX => SideDataKind::DisplayMatrix,
+        AVPacketSideDataType_AV_PKT_DATA_STEREO3D => SideDataKind::Stereo3d,
+        AVPacketSideDataType_AV_PKT_DATA_SKIP_SAMPLES => SideDataKind::SkipSamples,
+        AVPacketSideDataType_AV_PKT_DATA_MASTERING_DISPLAY_METADATA => {
+            SideDataKind::MasteringDisplayMetadata
+        }
+        other => SideDataKind::Other(other),
+    }
+}
@@ -0,0 +1,288 @@
+use std::ffi::CString;
+use std::io::{Seek, Write};
+use std::os::raw::c_void;
+use std::ptr;
+
+use super::binding::avcodec::{EINVAL, ENOMEM};
+use super::binding::avformat::{
+    self, AVFormatContext, AVIOContext, AVMediaType_AVMEDIA_TYPE_AUDIO,
+    AVMediaType_AVMEDIA_TYPE_VIDEO, AVFMT_FLAG_CUSTOM_IO, AVFMT_NOFILE, AVIO_FLAG_WRITE,
+};
+use super::binding::avutil::AV_NOPTS_VALUE;
+use super::bsf::BitstreamFilter;
+use super::error::*;
+use super::format::Format;
+use super::packet::Packet;
+use super::wrapper::format_wrapper::*;
+
+enum OutputTarget<'w> {
+    File(String),
+    Writer(&'w mut dyn WriteSeek),
+}
+
+pub struct OutputSpec<'w> {
+    target: OutputTarget<'w>,
+    format: Option<String>,
+    streams: Option<Vec<i32>>,
+    filters: Vec<(i32, String)>,
+    options: Vec<(String, String)>,
+}
+
+impl<'w> OutputSpec<'w> {
+    pub fn file(path: &str) -> Self {
+        Self::new(OutputTarget::File(path.to_string()), None)
+    }
+
+    pub fn writer<W: Write + Seek>(writer: &'w mut W, format: &str) -> Self {
+        Self::new(OutputTarget::Writer(writer), Some(format.to_string()))
+    }
+
+    fn new(target: OutputTarget<'w>, format: Option<String>) -> Self {
+        OutputSpec {
+            target,
+            format,
+            streams: None,
+            filters: vec![],
+            options: vec![],
+        }
+    }
+
+    pub fn format(mut self, name: &str) -> Self {
+        self.format = Some(name.to_string());
+        self
+    }
+
+    pub fn streams(mut self, indices: &[i32]) -> Self {
+        self.streams = Some(indices.to_vec());
+        self
+    }
+
+    pub fn bitstream_filter(mut self, index: i32, filters: &str) -> Self {
+        self.filters.push((index, filters.to_string()));
+        self
+    }
+
+    pub fn option(mut self, key: &str, value: &str) -> Self {
+        self.options.push((key.to_string(), value.to_string()));
+        self
+    }
+}
+
+struct Output<'w> {
+    fmtctx: *mut AVFormatContext,
+    ioctx: *mut AVIOContext,
+    writer: *mut WrapperWriter<'w>,
+}
+
+impl<'w> Output<'w> {
+    fn error(&self, code: i32, desc: &str) -> FFmpegError {
+        if !self.writer.is_null() {
+            if let Some(msg) = unsafe { &(*self.writer).error } {
+                return FFmpegError::from_io(desc, msg);
+            }
+        }
+        FFmpegError::new(code, desc)
+    }
+
+    unsafe fn write(&mut self, mut packet: Packet, index: i32) -> Result<(), FFmpegError> {
+        let streams =
+            std::slice::from_raw_parts((*self.fmtctx).streams, (*self.fmtctx).nb_streams as usize);
+        let pkt = packet.as_mut_ptr();
+        avformat::av_packet_rescale_ts(
+            pkt,
+            packet.raw_time_base(),
+            (*streams[index as usize]).time_base,
+        );
+        (*pkt).stream_index = index;
+        (*pkt).pos = -1;
+
+        let ret = avformat::av_interleaved_write_frame(self.fmtctx, pkt);
+        if ret < 0 {
+            return Err(self.error(ret, "remux_write_packet"));
+        }
+        Ok(())
+    }
+}
+
+impl<'w> Drop for Output<'w> {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.ioctx.is_null() {
+                wrapper_free_ioctx(self.ioctx);
+                self.ioctx = ptr::null_mut();
+            } else if !self.fmtctx.is_null() && !(*self.fmtctx).pb.is_null() {
+                avformat::avio_closep(&mut (*self.fmtctx).pb);
+            }
+            if !self.writer.is_null() {
+                drop(Box::from_raw(self.writer));
+                self.writer = ptr::null_mut();
+            }
+            if !self.fmtctx.is_null() {
+                avformat::avformat_free_context(self.fmtctx);
+                self.fmtctx = ptr::null_mut();
+            }
+        }
+    }
+}
+
+pub fn remux(input: &mut Format, output: OutputSpec) -> Result<(), FFmpegError> {
+    let OutputSpec {
+        target,
+        format,
+        streams,
+        filters,
+        options,
+    } = output;
+
+    let mut out = Output {
+        fmtctx: ptr::null_mut(),
+        ioctx: ptr::null_mut(),
+        writer: ptr::null_mut(),
+    };
+
+    unsafe {
+        let c_format = format.map(|f| CString::new(f).unwrap());
+        let c_path = match target {
+            OutputTarget::File(ref path) => Some(CString::new(path.as_str()).unwrap()),
+            OutputTarget::Writer(_) => None,
+        };
+        let ret = avformat::avformat_alloc_output_context2(
+            &mut out.fmtctx,
+            ptr::null_mut(),
+            c_format.as_ref().map_or(ptr::null(), |f| f.as_ptr()),
+            c_path.as_ref().map_or(ptr::null(), |p| p.as_ptr()),
+        );
+        if ret < 0 {
+            return Err(FFmpegError::new(ret, "remux_alloc_output"));
+        }
+
+        match target {
+            OutputTarget::File(_) => {
+                if (*(*out.fmtctx).oformat).flags & AVFMT_NOFILE as i32 == 0 {
+                    let path = c_path.as_ref().unwrap();
+                    let ret = avformat::avio_open(
+                        &mut (*out.fmtctx).pb,
+                        path.as_ptr(),
+                        AVIO_FLAG_WRITE as i32,
+                    );
+                    if ret < 0 {
+                        return Err(FFmpegError::new(ret, "remux_open_output"));
+                    }
+                }
+            }
+            OutputTarget::Writer(inner) => {
+                out.writer = Box::into_raw(Box::new(WrapperWriter { inner, error: None }));
+                let buffer = avformat::av_malloc(WRAPPER_BUFFER_SIZE as usize) as *mut u8;
+                out.ioctx = avformat::avio_alloc_context(
+                    buffer,
+                    WRAPPER_BUFFER_SIZE,
+                    1,
+                    out.writer as *mut c_void,
+                    None,
+                    Some(write_writer),
+                    Some(seek_writer),
+                );
+                (*out.fmtctx).pb = out.ioctx;
+                (*out.fmtctx).flags |= AVFMT_FLAG_CUSTOM_IO as i32;
+            }
+        }
+
+        let n = (*input.fmtctx).nb_streams as usize;
+        let in_streams = std::slice::from_raw_parts((*input.fmtctx).streams, n);
+        let mut mapping = vec![-1; n];
+        let mut bsfs: Vec<Option<BitstreamFilter>> = (0..n).map(|_| None).collect();
+        for (i, in_stream) in in_streams.iter().enumerate() {
+            let selected = match streams {
+                Some(ref indices) => indices.contains(&(i as i32)),
+                None => {
+                    let codec_type = (*(**in_stream).codecpar).codec_type;
+                    codec_type == AVMediaType_AVMEDIA_TYPE_VIDEO
+                        || codec_type == AVMediaType_AVMEDIA_TYPE_AUDIO
+                }
+            };
+            if !selected {
+                continue;
+            }
+
+            let out_stream = avformat::avformat_new_stream(out.fmtctx, ptr::null());
+            if out_stream.is_null() {
+                return Err(FFmpegError::new(-(ENOMEM as i32), "remux_new_stream"));
+            }
+
+            let mut par = (**in_stream).codecpar;
+            (*out_stream).time_base = (**in_stream).time_base;
+            if let Some((_, ref f)) = filters.iter().find(|(idx, _)| *idx == i as i32) {
+                let bsf = BitstreamFilter::from_handle(f, *in_stream)?;
+                par = bsf.parameters();
+                let (num, den) = bsf.time_base();
+                (*out_stream).time_base.num = num;
+                (*out_stream).time_base.den = den;
+                bsfs[i] = Some(bsf);
+            }
+
+            let ret = avformat::avcodec_parameters_copy((*out_stream).codecpar, par);
+            if ret < 0 {
+                return Err(FFmpegError::new(ret, "remux_copy_parameters"));
+            }
+            (*(*out_stream).codecpar).codec_tag = 0;
+            mapping[i] = (*out_stream).index;
+        }
+        if (*out.fmtctx).nb_streams == 0 {
+            return Err(FFmpegError::new(-(EINVAL as i32), "remux_no_streams"));
+        }
+
+        // decoding may already have consumed part of the input; a non-seekable input is
+        // remuxed from wherever it currently is
+        if input.is_seekable() {
+            let start = match (*input.fmtctx).start_time {
+                AV_NOPTS_VALUE => 0,
+                start => start,
+            };
+            let ret = avformat::avformat_seek_file(input.fmtctx, -1, i64::MIN, start, start, 0);
+            if ret < 0 {
+                return Err(input.error(ret, "remux_rewind"));
+            }
+        }
+
+        let mut opts = wrapper_dict_from_pairs(&options);
+        let ret = avformat::avformat_write_header(out.fmtctx, &mut opts);
+        avformat::av_dict_free(&mut opts);
+        if ret < 0 {
+            return Err(out.error(ret, "remux_write_header"));
+        }
+
+        loop {
+            let packet = match input.read_packet() {
+                Ok(packet) => packet,
+                Err(ref err) if err.kind() == ErrorKind::Eof => break,
+                Err(err) => return Err(err),
+            };
+            let i = packet.stream_index() as usize;
+            if i >= n || mapping[i] < 0 {
+                continue;
+            }
+            match bsfs[i] {
+                Some(ref mut bsf) => {
+                    for packet in bsf.filter(packet)? {
+                        out.write(packet, mapping[i])?;
+                    }
+                }
+                None => out.write(packet, mapping[i])?,
+            }
+        }
+
+        for (i, bsf) in bsfs.iter_mut().enumerate() {
+            if let Some(ref mut bsf) = bsf {
+                for packet in bsf.flush()? {
+                    out.write(packet, mapping[i])?;
+                }
+            }
+        }
+
+        let ret = avformat::av_write_trailer(out.fmtctx);
+        if ret < 0 {
+            return Err(out.error(ret, "remux_write_trailer"));
+        }
+    }
+    Ok(())
+}
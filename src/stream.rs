@@ -1,11 +1,31 @@
+use std::cell::Cell;
+use std::ffi::CStr;
+use std::marker::PhantomData;
 use std::os::raw::c_void;
 use std::ptr;
+use std::time::Duration;
 
+use super::binding::avcodec::{EBUSY, EINVAL, ENOMEM};
 use super::binding::avformat::{
-    self, AVMediaType_AVMEDIA_TYPE_AUDIO, AVMediaType_AVMEDIA_TYPE_VIDEO, AVStream,
+    self, AVDiscard, AVDiscard_AVDISCARD_ALL, AVDiscard_AVDISCARD_BIDIR,
+    AVDiscard_AVDISCARD_DEFAULT, AVDiscard_AVDISCARD_NONE, AVDiscard_AVDISCARD_NONINTRA,
+    AVDiscard_AVDISCARD_NONKEY, AVDiscard_AVDISCARD_NONREF, AVFrame,
+    AVMediaType_AVMEDIA_TYPE_AUDIO, AVMediaType_AVMEDIA_TYPE_VIDEO, AVPixelFormat,
+    AVPixelFormat_AV_PIX_FMT_BGR24, AVPixelFormat_AV_PIX_FMT_BGRA, AVPixelFormat_AV_PIX_FMT_GRAY8,
+    AVPixelFormat_AV_PIX_FMT_NV12, AVPixelFormat_AV_PIX_FMT_RGB24,
+    AVPixelFormat_AV_PIX_FMT_RGB48LE, AVPixelFormat_AV_PIX_FMT_RGBA,
+    AVPixelFormat_AV_PIX_FMT_YUV420P, AVRational, AVStream, AVSEEK_FLAG_BACKWARD, FF_THREAD_FRAME,
+    FF_THREAD_SLICE,
+};
+use super::binding::avutil::AV_NOPTS_VALUE;
+use super::binding::imgutils;
+use super::binding::swscale::{
+    SWS_AREA, SWS_BICUBIC, SWS_BILINEAR, SWS_FAST_BILINEAR, SWS_LANCZOS, SWS_POINT,
 };
 use super::error::*;
 use super::format::Format;
+use super::frame::{frame_meta, FrameMeta};
+use super::index::{build_index, FrameIndex};
 use super::wrapper::stream_wrapper::*;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -14,44 +34,361 @@ pub enum Orientation {
     Bottom,
     Left,
     Right,
+    TopMirrored,
+    BottomMirrored,
+    LeftMirrored,
+    RightMirrored,
+}
+
+impl Orientation {
+    pub fn degrees(self) -> i32 {
+        match self {
+            Orientation::Top | Orientation::TopMirrored => 0,
+            Orientation::Left | Orientation::LeftMirrored => 90,
+            Orientation::Bottom | Orientation::BottomMirrored => 180,
+            Orientation::Right | Orientation::RightMirrored => 270,
+        }
+    }
+
+    pub fn is_mirrored(self) -> bool {
+        matches!(
+            self,
+            Orientation::TopMirrored
+                | Orientation::BottomMirrored
+                | Orientation::LeftMirrored
+                | Orientation::RightMirrored
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PixelFormat {
+    Rgb24,
+    Bgr24,
+    Rgba,
+    Bgra,
+    Gray8,
+    Yuv420p,
+    Nv12,
+    Rgb48,
+}
+
+impl PixelFormat {
+    fn to_raw(self) -> AVPixelFormat {
+        match self {
+            PixelFormat::Rgb24 => AVPixelFormat_AV_PIX_FMT_RGB24,
+            PixelFormat::Bgr24 => AVPixelFormat_AV_PIX_FMT_BGR24,
+            PixelFormat::Rgba => AVPixelFormat_AV_PIX_FMT_RGBA,
+            PixelFormat::Bgra => AVPixelFormat_AV_PIX_FMT_BGRA,
+            PixelFormat::Gray8 => AVPixelFormat_AV_PIX_FMT_GRAY8,
+            PixelFormat::Yuv420p => AVPixelFormat_AV_PIX_FMT_YUV420P,
+            PixelFormat::Nv12 => AVPixelFormat_AV_PIX_FMT_NV12,
+            PixelFormat::Rgb48 => AVPixelFormat_AV_PIX_FMT_RGB48LE,
+        }
+    }
+
+    #[allow(non_upper_case_globals)]
+    fn from_raw(fmt: AVPixelFormat) -> Option<PixelFormat> {
+        match fmt {
+            AVPixelFormat_AV_PIX_FMT_RGB24 => Some(PixelFormat::Rgb24),
+            AVPixelFormat_AV_PIX_FMT_BGR24 => Some(PixelFormat::Bgr24),
+            AVPixelFormat_AV_PIX_FMT_RGBA => Some(PixelFormat::Rgba),
+            AVPixelFormat_AV_PIX_FMT_BGRA => Some(PixelFormat::Bgra),
+            AVPixelFormat_AV_PIX_FMT_GRAY8 => Some(PixelFormat::Gray8),
+            AVPixelFormat_AV_PIX_FMT_YUV420P => Some(PixelFormat::Yuv420p),
+            AVPixelFormat_AV_PIX_FMT_NV12 => Some(PixelFormat::Nv12),
+            AVPixelFormat_AV_PIX_FMT_RGB48LE => Some(PixelFormat::Rgb48),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Resize {
+    Exact(i32, i32),
+    Fit(i32, i32),
+    Scale(f32),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ScaleAlgorithm {
+    FastBilinear,
+    Bilinear,
+    Bicubic,
+    Lanczos,
+    Area,
+    Point,
+}
+
+impl ScaleAlgorithm {
+    fn to_raw(self) -> i32 {
+        let flags = match self {
+            ScaleAlgorithm::FastBilinear => SWS_FAST_BILINEAR,
+            ScaleAlgorithm::Bilinear => SWS_BILINEAR,
+            ScaleAlgorithm::Bicubic => SWS_BICUBIC,
+            ScaleAlgorithm::Lanczos => SWS_LANCZOS,
+            ScaleAlgorithm::Area => SWS_AREA,
+            ScaleAlgorithm::Point => SWS_POINT,
+        };
+        flags as i32
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ThreadType {
+    Frame,
+    Slice,
+    FrameAndSlice,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Threading {
+    pub count: i32,
+    pub thread_type: ThreadType,
+}
+
+impl Threading {
+    pub fn new(count: i32, thread_type: ThreadType) -> Threading {
+        Threading { count, thread_type }
+    }
+
+    pub fn all_cores() -> Threading {
+        Threading::new(0, ThreadType::FrameAndSlice)
+    }
+
+    pub fn single_threaded() -> Threading {
+        Threading::new(1, ThreadType::Slice)
+    }
+
+    fn raw_thread_type(self) -> i32 {
+        let flags = match self.thread_type {
+            ThreadType::Frame => FF_THREAD_FRAME,
+            ThreadType::Slice => FF_THREAD_SLICE,
+            ThreadType::FrameAndSlice => FF_THREAD_FRAME | FF_THREAD_SLICE,
+        };
+        flags as i32
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Discard {
+    None,
+    Default,
+    NonRef,
+    Bidir,
+    NonIntra,
+    NonKey,
+    All,
+}
+
+impl Discard {
+    fn to_raw(self) -> AVDiscard {
+        match self {
+            Discard::None => AVDiscard_AVDISCARD_NONE,
+            Discard::Default => AVDiscard_AVDISCARD_DEFAULT,
+            Discard::NonRef => AVDiscard_AVDISCARD_NONREF,
+            Discard::Bidir => AVDiscard_AVDISCARD_BIDIR,
+            Discard::NonIntra => AVDiscard_AVDISCARD_NONINTRA,
+            Discard::NonKey => AVDiscard_AVDISCARD_NONKEY,
+            Discard::All => AVDiscard_AVDISCARD_ALL,
+        }
+    }
+
+    #[allow(non_upper_case_globals)]
+    fn from_raw(discard: AVDiscard) -> Discard {
+        match discard {
+            AVDiscard_AVDISCARD_NONE => Discard::None,
+            AVDiscard_AVDISCARD_NONREF => Discard::NonRef,
+            AVDiscard_AVDISCARD_BIDIR => Discard::Bidir,
+            AVDiscard_AVDISCARD_NONINTRA => Discard::NonIntra,
+            AVDiscard_AVDISCARD_NONKEY => Discard::NonKey,
+            AVDiscard_AVDISCARD_ALL => Discard::All,
+            _ => Discard::Default,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SeekMode {
+    Accurate,
+    Keyframe,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SampleMode {
+    Nearest,
+    AtOrBefore,
 }
 
 #[derive(Debug)]
-pub struct Stream {
+pub struct Stream<'a> {
     pub orientation: Orientation,
     pub n_frame: i32,
     pub fps: f32,
     pub duration: f32,
-    pub format: *mut Format,
+    pub format: *mut Format<'a>,
     pub time_base: f32,
     kind: i32,
+    pixel_format: PixelFormat,
+    resize: Option<Resize>,
+    scale_algorithm: ScaleAlgorithm,
+    auto_rotate: bool,
+    pending_seek: Cell<Option<i64>>,
+    frame_index: Option<FrameIndex>,
     decode_ctx: WrapperDecodeCtx,
     index: i32,
     handle: *mut AVStream,
+    _marker: PhantomData<&'a [u8]>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Plane {
+    pub offset: usize,
+    pub stride: i32,
+    pub height: i32,
+}
+
+#[derive(Debug, Clone)]
 pub struct Frame {
     pub buffer: Vec<u8>,
+    pub format: PixelFormat,
+    pub planes: Vec<Plane>,
     pub width: i32,
     pub height: i32,
     pub stride: i32,
     pub pts: f32,
     pub dts: f32,
+    pub meta: FrameMeta,
+    pub source_changed: bool,
 }
 
-pub unsafe fn new_stream(handle: *mut AVStream, format: *mut Format, kind: i32) -> Stream {
-    let mut meta: WrapperStreamMeta = std::mem::uninitialized();
+#[derive(Debug, Clone)]
+pub struct FrameInfo {
+    pub format: PixelFormat,
+    pub planes: Vec<Plane>,
+    pub size: usize,
+    pub width: i32,
+    pub height: i32,
+    pub stride: i32,
+    pub pts: f32,
+    pub dts: f32,
+    pub meta: FrameMeta,
+    pub source_changed: bool,
+}
+
+impl Frame {
+    pub fn plane(&self, i: usize) -> &[u8] {
+        let plane = &self.planes[i];
+        let size = (plane.stride * plane.height) as usize;
+        &self.buffer[plane.offset..plane.offset + size]
+    }
+}
+
+pub struct FrameRef<'s> {
+    frame: *const AVFrame,
+    time_base: AVRational,
+    pts: f32,
+    dts: f32,
+    _marker: PhantomData<&'s AVFrame>,
+}
+
+impl<'s> FrameRef<'s> {
+    pub fn width(&self) -> i32 {
+        unsafe { (*self.frame).width }
+    }
+
+    pub fn height(&self) -> i32 {
+        unsafe { (*self.frame).height }
+    }
+
+    pub fn pixel_format(&self) -> Option<PixelFormat> {
+        PixelFormat::from_raw(unsafe { (*self.frame).format })
+    }
+
+    pub fn format_name(&self) -> &str {
+        unsafe {
+            let name = imgutils::av_get_pix_fmt_name((*self.frame).format);
+            if name.is_null() {
+                return "";
+            }
+            CStr::from_ptr(name).to_str().unwrap_or("")
+        }
+    }
+
+    pub fn n_planes(&self) -> usize {
+        let n = unsafe { imgutils::av_pix_fmt_count_planes((*self.frame).format) };
+        n.max(0) as usize
+    }
+
+    pub fn linesize(&self, i: usize) -> i32 {
+        unsafe { (*self.frame).linesize[i] }
+    }
+
+    pub fn plane(&self, i: usize) -> &[u8] {
+        assert!(i < self.n_planes(), "plane index out of range");
+        unsafe {
+            let frame = &*self.frame;
+            let mut height = frame.height;
+            if i == 1 || i == 2 {
+                let desc = imgutils::av_pix_fmt_desc_get(frame.format);
+                height = -((-height) >> (*desc).log2_chroma_h);
+            }
+            let linesize = frame.linesize[i] as isize;
+            let mut data = frame.data[i] as *const u8;
+            if linesize < 0 {
+                data = data.offset(linesize * (height as isize - 1));
+            }
+            std::slice::from_raw_parts(data, linesize.unsigned_abs() * height as usize)
+        }
+    }
+
+    pub fn pts(&self) -> f32 {
+        self.pts
+    }
+
+    pub fn dts(&self) -> f32 {
+        self.dts
+    }
+
+    pub fn meta(&self) -> FrameMeta {
+        unsafe { frame_meta(self.frame, self.time_base) }
+    }
+}
+
+impl<'s> std::fmt::Debug for FrameRef<'s> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("FrameRef")
+            .field("format", &self.format_name())
+            .field("width", &self.width())
+            .field("height", &self.height())
+            .field("pts", &self.pts)
+            .field("dts", &self.dts)
+            .finish()
+    }
+}
+
+pub unsafe fn new_stream<'a>(
+    handle: *mut AVStream,
+    format: *mut Format<'a>,
+    kind: i32,
+) -> Stream<'a> {
+    let mut meta: WrapperStreamMeta = std::mem::zeroed();
     wrapper_get_meta(handle, kind, &mut meta);
 
     Stream {
         fps: meta.fps,
-        orientation: degree_to_orientation(meta.rtt),
+        orientation: degree_to_orientation(meta.rtt, meta.mirror != 0),
         n_frame: meta.nfr,
         format: format,
         duration: meta.dur,
         time_base: meta.tb,
         kind: kind,
+        pixel_format: PixelFormat::Bgr24,
+        resize: None,
+        scale_algorithm: ScaleAlgorithm::FastBilinear,
+        auto_rotate: false,
+        pending_seek: Cell::new(None),
+        frame_index: None,
         handle: handle,
         index: meta.idx,
         decode_ctx: WrapperDecodeCtx {
@@ -59,24 +396,52 @@ pub unsafe fn new_stream(handle: *mut AVStream, format: *mut Format, kind: i32)
             frame: ptr::null_mut(),
             packet: ptr::null_mut(),
             output: WrapperFrameOutput {
-                bgr24_buf: [ptr::null_mut(); 4usize],
+                pix_fmt: PixelFormat::Bgr24.to_raw(),
+                sws_flags: ScaleAlgorithm::FastBilinear.to_raw(),
+                resize_mode: WRAPPER_RESIZE_NONE,
+                resize_width: 0,
+                resize_height: 0,
+                resize_factor: 1.0,
+                rotation: 0,
+                mirror: 0,
+                rotate_buf: [ptr::null_mut(); 4usize],
+                rotate_linesize: [0; 4usize],
+                image_buf: [ptr::null_mut(); 4usize],
                 linesize: [0; 4usize],
+                dst_buf: [ptr::null_mut(); 4usize],
+                dst_linesize: [0; 4usize],
                 allocated: 0,
                 buffer: ptr::null_mut(),
                 img_convert_ctx: ptr::null_mut(),
                 buffersize: 0,
+                src_width: 0,
+                src_height: 0,
+                src_format: -1,
+                changed: 0,
                 width: 0,
                 height: 0,
                 stride: 0,
             },
+            thread_count: -1,
+            thread_type: -1,
+            skip_frame: AVDiscard_AVDISCARD_DEFAULT,
+            skip_loop_filter: AVDiscard_AVDISCARD_DEFAULT,
+            skip_idct: AVDiscard_AVDISCARD_DEFAULT,
+            lowres: 0,
+            key_only: 0,
+            seek_target: AV_NOPTS_VALUE,
+            last_pts: AV_NOPTS_VALUE,
+            draining: 0,
+            held: 0,
             need_sent: 0,
             pts: 0.0,
             dts: 0.0,
         },
+        _marker: PhantomData,
     }
 }
 
-impl Drop for Stream {
+impl<'a> Drop for Stream<'a> {
     fn drop(&mut self) {
         let ctx = &mut self.decode_ctx;
         if !ctx.cctx.is_null() {
@@ -86,26 +451,368 @@ impl Drop for Stream {
     }
 }
 
-impl Stream {
+impl<'a> Stream<'a> {
+    pub fn index(&self) -> i32 {
+        self.index
+    }
+
+    pub(crate) fn handle(&self) -> *mut AVStream {
+        self.handle
+    }
+
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.pixel_format
+    }
+
+    pub fn set_pixel_format(&mut self, format: PixelFormat) {
+        if format == self.pixel_format {
+            return;
+        }
+        self.pixel_format = format;
+        let output = &mut self.decode_ctx.output;
+        unsafe { wrapper_reset_output(output) };
+        output.pix_fmt = format.to_raw();
+    }
+
+    pub fn resize(&self) -> Option<Resize> {
+        self.resize
+    }
+
+    pub fn set_resize(&mut self, resize: Option<Resize>) -> Result<(), FFmpegError> {
+        let (mode, width, height, factor) = match resize {
+            None => (WRAPPER_RESIZE_NONE, 0, 0, 1.0),
+            Some(Resize::Exact(w, h)) if w > 0 && h > 0 => (WRAPPER_RESIZE_EXACT, w, h, 1.0),
+            Some(Resize::Fit(w, h)) if w > 0 && h > 0 => (WRAPPER_RESIZE_FIT, w, h, 1.0),
+            Some(Resize::Scale(f)) if f > 0.0 && f.is_finite() => (WRAPPER_RESIZE_SCALE, 0, 0, f),
+            Some(_) => return Err(FFmpegError::new(-(EINVAL as i32), "invalid resize")),
+        };
+        if resize == self.resize {
+            return Ok(());
+        }
+        self.resize = resize;
+        let output = &mut self.decode_ctx.output;
+        unsafe { wrapper_reset_output(output) };
+        output.resize_mode = mode;
+        output.resize_width = width;
+        output.resize_height = height;
+        output.resize_factor = factor;
+        Ok(())
+    }
+
+    pub fn scale_algorithm(&self) -> ScaleAlgorithm {
+        self.scale_algorithm
+    }
+
+    pub fn set_scale_algorithm(&mut self, algorithm: ScaleAlgorithm) {
+        if algorithm == self.scale_algorithm {
+            return;
+        }
+        self.scale_algorithm = algorithm;
+        let output = &mut self.decode_ctx.output;
+        unsafe { wrapper_reset_output(output) };
+        output.sws_flags = algorithm.to_raw();
+    }
+
+    pub fn threading(&self) -> Option<Threading> {
+        let ctx = &self.decode_ctx;
+        if ctx.thread_count < 0 {
+            return None;
+        }
+        let thread_type = match ctx.thread_type as u32 {
+            FF_THREAD_FRAME => ThreadType::Frame,
+            FF_THREAD_SLICE => ThreadType::Slice,
+            _ => ThreadType::FrameAndSlice,
+        };
+        Some(Threading::new(ctx.thread_count, thread_type))
+    }
+
+    pub fn set_threading(&mut self, threading: Threading) -> Result<(), FFmpegError> {
+        if threading.count < 0 {
+            return Err(FFmpegError::new(-(EINVAL as i32), "invalid thread count"));
+        }
+        if !self.decode_ctx.cctx.is_null() {
+            return Err(FFmpegError::new(-(EBUSY as i32), "decoder already opened"));
+        }
+        self.decode_ctx.thread_count = threading.count;
+        self.decode_ctx.thread_type = threading.raw_thread_type();
+        Ok(())
+    }
+
+    pub fn skip_frame(&self) -> Discard {
+        Discard::from_raw(self.decode_ctx.skip_frame)
+    }
+
+    pub fn set_skip_frame(&mut self, discard: Discard) {
+        self.decode_ctx.skip_frame = discard.to_raw();
+        unsafe { wrapper_apply_skip_options(&mut self.decode_ctx) };
+    }
+
+    pub fn skip_loop_filter(&self) -> Discard {
+        Discard::from_raw(self.decode_ctx.skip_loop_filter)
+    }
+
+    pub fn set_skip_loop_filter(&mut self, discard: Discard) {
+        self.decode_ctx.skip_loop_filter = discard.to_raw();
+        unsafe { wrapper_apply_skip_options(&mut self.decode_ctx) };
+    }
+
+    pub fn skip_idct(&self) -> Discard {
+        Discard::from_raw(self.decode_ctx.skip_idct)
+    }
+
+    pub fn set_skip_idct(&mut self, discard: Discard) {
+        self.decode_ctx.skip_idct = discard.to_raw();
+        unsafe { wrapper_apply_skip_options(&mut self.decode_ctx) };
+    }
+
+    pub fn lowres(&self) -> i32 {
+        self.decode_ctx.lowres
+    }
+
+    pub fn max_lowres(&self) -> i32 {
+        unsafe {
+            let codec = avformat::avcodec_find_decoder((*(*self.handle).codecpar).codec_id);
+            if codec.is_null() {
+                return 0;
+            }
+            avformat::av_codec_get_max_lowres(codec)
+        }
+    }
+
+    pub fn set_lowres(&mut self, lowres: i32) -> Result<(), FFmpegError> {
+        if !(0..=3).contains(&lowres) {
+            return Err(FFmpegError::new(-(EINVAL as i32), "invalid lowres"));
+        }
+        // avcodec_open2 silently clamps to the codec maximum, which is 0 for H.264/HEVC
+        if lowres > self.max_lowres() {
+            return Err(FFmpegError::new(-(EINVAL as i32), "lowres not supported"));
+        }
+        if !self.decode_ctx.cctx.is_null() {
+            return Err(FFmpegError::new(-(EBUSY as i32), "decoder already opened"));
+        }
+        self.decode_ctx.lowres = lowres;
+        Ok(())
+    }
+
+    pub fn keyframes(&mut self) -> Keyframes<'_, 'a> {
+        let skip_frame = self.decode_ctx.skip_frame;
+        self.decode_ctx.key_only = 1;
+        self.set_skip_frame(Discard::NonKey);
+        Keyframes {
+            stream: self,
+            skip_frame,
+            done: false,
+        }
+    }
+
+    pub fn rotation(&self) -> i32 {
+        self.orientation.degrees()
+    }
+
+    pub fn is_mirrored(&self) -> bool {
+        self.orientation.is_mirrored()
+    }
+
+    pub fn auto_rotate(&self) -> bool {
+        self.auto_rotate
+    }
+
+    pub fn set_auto_rotate(&mut self, enabled: bool) {
+        let (rotation, mirror) = if enabled {
+            (self.rotation(), self.is_mirrored() as i32)
+        } else {
+            (0, 0)
+        };
+        self.auto_rotate = enabled;
+        let output = &mut self.decode_ctx.output;
+        if rotation != output.rotation || mirror != output.mirror {
+            unsafe { wrapper_reset_output(output) };
+            output.rotation = rotation;
+            output.mirror = mirror;
+        }
+    }
+
     pub fn seek_by_time(&self, t: f32) -> Result<(), FFmpegError> {
+        self.seek_by_time_with(t, SeekMode::Accurate)
+    }
+
+    pub fn seek_by_time_with(&self, t: f32, mode: SeekMode) -> Result<(), FFmpegError> {
+        self.seek(t, mode, "ffmpeg_seek_time")
+    }
+
+    pub fn seek_by_frame(&self, pos: i32) -> Result<(), FFmpegError> {
+        let pts = self
+            .frame_index
+            .as_ref()
+            .and_then(|index| index.pts(pos as usize));
+        match pts {
+            Some(pts) if pos >= 0 => self.seek_to_pts(pts, "ffmpeg_seek_frame"),
+            _ => self.seek(
+                pos as f32 / self.fps,
+                SeekMode::Accurate,
+                "ffmpeg_seek_frame",
+            ),
+        }
+    }
+
+    pub fn frame_index(&self) -> Option<&FrameIndex> {
+        self.frame_index.as_ref()
+    }
+
+    pub(crate) fn load_index(&mut self, index: FrameIndex) {
+        self.frame_index = Some(index);
+    }
+
+    // scanning packets rewinds the input, so decoding resumes at a pending seek target or
+    // right after the last decoded frame, and from the stream start if there is neither
+    pub fn build_index(&mut self) -> Result<&FrameIndex, FFmpegError> {
+        if self.frame_index.is_none() {
+            let resume = match self.pending_seek.get() {
+                Some(target) => target,
+                None if self.decode_ctx.last_pts != AV_NOPTS_VALUE => self.decode_ctx.last_pts + 1,
+                None => AV_NOPTS_VALUE,
+            };
+            let index = unsafe { build_index(self.format, self.handle, self.decode_ctx.cctx)? };
+            self.frame_index = Some(index);
+            if unsafe { (*self.format).is_seekable() } {
+                match resume {
+                    AV_NOPTS_VALUE => self.pending_seek.set(Some(AV_NOPTS_VALUE)),
+                    pts => self.seek_to_pts(pts, "ffmpeg_build_index")?,
+                }
+            }
+        }
+        Ok(self.frame_index.as_ref().unwrap())
+    }
+
+    pub fn frame_at(&mut self, n: usize) -> Result<Frame, FFmpegError> {
+        let pts = match self.build_index()?.pts(n) {
+            Some(pts) => pts,
+            None => {
+                return Err(FFmpegError::new(
+                    -(EINVAL as i32),
+                    "frame number out of range",
+                ))
+            }
+        };
+        self.seek_to_pts(pts, "ffmpeg_frame_at")?;
+        self.next_video_frame()
+    }
+
+    pub fn frames_at(&mut self, times: &[Duration]) -> FramesAt<'_, 'a> {
+        let targets: Vec<i64> = times.iter().map(|&t| self.time_to_pts(t)).collect();
+        let mut order: Vec<usize> = (0..targets.len()).collect();
+        order.sort_by_key(|&i| targets[i]);
+        FramesAt {
+            stream: self,
+            results: targets.iter().map(|_| None).collect(),
+            targets,
+            order,
+            next_target: 0,
+            next_result: 0,
+            last: None,
+        }
+    }
+
+    pub fn sample_at_fps(&mut self, rate: f64) -> Result<Samples<'_, 'a>, FFmpegError> {
+        self.sample_at_fps_with(rate, SampleMode::Nearest)
+    }
+
+    pub fn sample_at_fps_with(
+        &mut self,
+        rate: f64,
+        mode: SampleMode,
+    ) -> Result<Samples<'_, 'a>, FFmpegError> {
+        if !rate.is_finite() || rate <= 0.0 {
+            return Err(FFmpegError::new(-(EINVAL as i32), "invalid sample rate"));
+        }
+        Ok(Samples {
+            stream: self,
+            rate,
+            mode,
+            tick: 0,
+            started: false,
+            prev: None,
+            next: None,
+            eof: false,
+            done: false,
+        })
+    }
+
+    fn pts_to_time(&self, pts: i64) -> f64 {
+        let stream = unsafe { &*self.handle };
+        let tb = stream.time_base;
+        let pts = match stream.start_time {
+            AV_NOPTS_VALUE => pts,
+            start => pts - start,
+        };
+        pts as f64 * tb.num as f64 / tb.den as f64
+    }
+
+    fn time_to_pts(&self, t: Duration) -> i64 {
+        let stream = unsafe { &*self.handle };
+        let tb = stream.time_base;
+        let pts = (t.as_secs_f64() * tb.den as f64 / tb.num as f64) as i64;
+        match stream.start_time {
+            AV_NOPTS_VALUE => pts,
+            start => pts + start,
+        }
+    }
+
+    fn keyframe_before_pts(&self, pts: i64) -> Option<i64> {
+        if let Some(index) = self.frame_index.as_ref() {
+            let n = index.frame_of_pts(pts + 1).checked_sub(1)?;
+            return index.keyframe_before(n).and_then(|k| index.pts(k));
+        }
+        let i = unsafe {
+            avformat::av_index_search_timestamp(self.handle, pts, AVSEEK_FLAG_BACKWARD as i32)
+        };
+        if i < 0 {
+            return None;
+        }
+        Some(unsafe { (*(*self.handle).index_entries.offset(i as isize)).timestamp })
+    }
+
+    fn seek_to_pts(&self, pts: i64, desc: &str) -> Result<(), FFmpegError> {
+        if unsafe { !(*self.format).is_seekable() } {
+            return Err(FFmpegError::not_seekable(desc));
+        }
         let ret = unsafe {
-            wrapper_stream_seek_by_time(self.handle, (*self.format).fmtctx, self.decode_ctx.cctx, t)
+            wrapper_stream_seek_by_ts(
+                self.handle,
+                (*self.format).fmtctx,
+                self.decode_ctx.cctx,
+                pts,
+            )
         };
-        match ret {
-            n if n < 0 => Err(FFmpegError::new(n, "ffmpeg_seek_time")),
-            _ => Ok(()),
+        if ret < 0 {
+            return Err(unsafe { (*self.format).error(ret, desc) });
         }
+        self.pending_seek.set(Some(pts));
+        Ok(())
     }
 
-    pub fn seek_by_frame(&self, pos: i32) -> Result<(), FFmpegError> {
-        let t = pos as f32 / self.fps;
+    fn seek(&self, t: f32, mode: SeekMode, desc: &str) -> Result<(), FFmpegError> {
+        if unsafe { !(*self.format).is_seekable() } {
+            return Err(FFmpegError::not_seekable(desc));
+        }
+        let mut target = AV_NOPTS_VALUE;
         let ret = unsafe {
-            wrapper_stream_seek_by_time(self.handle, (*self.format).fmtctx, self.decode_ctx.cctx, t)
+            wrapper_stream_seek_by_time(
+                self.handle,
+                (*self.format).fmtctx,
+                self.decode_ctx.cctx,
+                t,
+                &mut target,
+            )
         };
-        match ret {
-            n if n < 0 => Err(FFmpegError::new(n, "ffmpeg_seek_frame")),
-            _ => Ok(()),
+        if ret < 0 {
+            return Err(unsafe { (*self.format).error(ret, desc) });
         }
+        self.pending_seek.set(Some(match mode {
+            SeekMode::Accurate => target,
+            SeekMode::Keyframe => AV_NOPTS_VALUE,
+        }));
+        Ok(())
     }
 
     pub fn next_video_frame(&mut self) -> Result<Frame, FFmpegError> {
@@ -116,24 +823,130 @@ impl Stream {
         if let Err(err) = self.init_ctx() {
             return Err(err);
         }
+        self.apply_pending_seek();
 
         let ret =
             unsafe { extract_next_frame((*self.format).fmtctx, &mut self.decode_ctx, self.index) };
+        if ret < 0 {
+            return Err(unsafe { (*self.format).error(ret, "next_frame") });
+        }
+
+        Ok(self.output_frame())
+    }
+
+    fn output_frame(&mut self) -> Frame {
+        let info = self.frame_info();
+        let output = &self.decode_ctx.output;
+        Frame {
+            buffer: unsafe { std::slice::from_raw_parts(output.buffer, info.size).to_vec() },
+            format: info.format,
+            planes: info.planes,
+            width: info.width,
+            height: info.height,
+            stride: info.stride,
+            pts: info.pts,
+            dts: info.dts,
+            meta: info.meta,
+            source_changed: info.source_changed,
+        }
+    }
+
+    fn convert_frame(&mut self, frame: *mut AVFrame) -> Result<Frame, FFmpegError> {
+        let ctx = &mut self.decode_ctx;
+        let ret = unsafe { frame_to_rawdata(ctx.cctx, frame, &mut ctx.output) };
         if ret < 0 {
             return Err(FFmpegError::new(ret, "next_frame"));
         }
+        let mut out = self.output_frame();
+        let tb = unsafe { (*self.handle).time_base };
+        out.meta = unsafe { frame_meta(frame, tb) };
+        let pts = out.meta.pts.unwrap_or(0);
+        let dts = match unsafe { (*frame).pkt_dts } {
+            AV_NOPTS_VALUE => pts,
+            dts => dts,
+        };
+        out.pts = (pts as f64 * tb.num as f64 / tb.den as f64) as f32;
+        out.dts = (dts as f64 * tb.num as f64 / tb.den as f64) as f32;
+        Ok(out)
+    }
+
+    pub fn frame_size(&self) -> usize {
+        let mut width = 0;
+        let mut height = 0;
+        let size = unsafe {
+            let par = (*self.handle).codecpar;
+            let output = &self.decode_ctx.output;
+            let (src_width, src_height) = if output.src_width > 0 {
+                (output.src_width, output.src_height)
+            } else {
+                ((*par).width, (*par).height)
+            };
+            wrapper_output_image_size(output, src_width, src_height, &mut width, &mut height)
+        };
+        size.max(0) as usize
+    }
 
+    pub fn next_video_frame_into(&mut self, buf: &mut [u8]) -> Result<FrameInfo, FFmpegError> {
+        if self.kind != AVMediaType_AVMEDIA_TYPE_VIDEO {
+            return Err(FFmpegError::new(-1, "stream type mismatch"));
+        }
+
+        self.init_ctx()?;
+        self.apply_pending_seek();
+
+        let ret = unsafe {
+            extract_next_frame_into(
+                (*self.format).fmtctx,
+                &mut self.decode_ctx,
+                self.index,
+                buf.as_mut_ptr(),
+                buf.len().min(i32::MAX as usize) as i32,
+            )
+        };
+        if ret < 0 {
+            return Err(unsafe { (*self.format).error(ret, "next_frame") });
+        }
+        Ok(self.frame_info())
+    }
+
+    fn frame_info(&mut self) -> FrameInfo {
+        let source_changed = self.decode_ctx.output.changed != 0;
+        self.decode_ctx.output.changed = 0;
         let output = &self.decode_ctx.output;
-        Ok(Frame {
-            buffer: unsafe {
-                std::slice::from_raw_parts(output.buffer, (output.height * output.stride) as usize)
-                    .to_vec()
-            },
+        FrameInfo {
+            format: self.pixel_format,
+            planes: output_planes(output),
+            size: output.buffersize as usize,
             width: output.width,
             height: output.height,
             stride: output.stride,
             pts: self.decode_ctx.pts,
             dts: self.decode_ctx.dts,
+            meta: unsafe { frame_meta(self.decode_ctx.frame, (*self.handle).time_base) },
+            source_changed,
+        }
+    }
+
+    pub fn next_frame_ref(&mut self) -> Result<FrameRef<'_>, FFmpegError> {
+        if self.kind != AVMediaType_AVMEDIA_TYPE_VIDEO {
+            return Err(FFmpegError::new(-1, "stream type mismatch"));
+        }
+
+        self.init_ctx()?;
+        self.apply_pending_seek();
+
+        let ret =
+            unsafe { decode_next_frame((*self.format).fmtctx, &mut self.decode_ctx, self.index) };
+        if ret < 0 {
+            return Err(unsafe { (*self.format).error(ret, "next_frame") });
+        }
+
+        Ok(FrameRef {
+            frame: self.decode_ctx.frame,
+            time_base: unsafe { (*self.handle).time_base },
+            pts: self.decode_ctx.pts,
+            dts: self.decode_ctx.dts,
+            _marker: PhantomData,
         })
     }
 
@@ -152,8 +965,8 @@ impl Stream {
             return Err(FFmpegError::new(-1, "invalid sample rate"));
         }
 
-        let mut recv_buffer: *mut u8 = unsafe { std::mem::uninitialized() };
-        let mut buffer_size: i32 = unsafe { std::mem::uninitialized() };
+        let mut recv_buffer: *mut u8 = ptr::null_mut();
+        let mut buffer_size: i32 = 0;
         let ret = unsafe {
             wrapper_stream_decode_audio(
                 self.handle,
@@ -164,8 +977,21 @@ impl Stream {
                 &mut buffer_size,
             )
         };
+        // the decoder treats any read failure as end of input, don't return truncated audio
+        let desc = "ffmpeg_stream_decode_audio";
+        let failed = unsafe {
+            (*self.format)
+                .interrupted(desc)
+                .or_else(|| (*self.format).reader_failed(desc))
+        };
+        if let Some(err) = failed {
+            if !recv_buffer.is_null() {
+                unsafe { avformat::free(recv_buffer as *mut c_void) };
+            }
+            return Err(err);
+        }
         if ret < 0 {
-            return Err(FFmpegError::new(ret, "ffmpeg_stream_decode_audio"));
+            return Err(unsafe { (*self.format).error(ret, desc) });
         }
 
         let data =
@@ -176,6 +1002,14 @@ impl Stream {
         Ok(data)
     }
 
+    fn apply_pending_seek(&mut self) {
+        if let Some(target) = self.pending_seek.take() {
+            self.decode_ctx.seek_target = target;
+            self.decode_ctx.draining = 0;
+            self.decode_ctx.held = 0;
+        }
+    }
+
     fn init_ctx(&mut self) -> Result<(), FFmpegError> {
         if self.decode_ctx.cctx.is_null() {
             let ret =
@@ -188,12 +1022,281 @@ impl Stream {
     }
 }
 
-fn degree_to_orientation(deg: i32) -> Orientation {
-    match deg % 360 {
-        0 => Orientation::Top,
-        90 => Orientation::Left,
-        180 => Orientation::Bottom,
-        270 => Orientation::Right,
-        _ => Orientation::Top,
+pub struct Keyframes<'s, 'a> {
+    stream: &'s mut Stream<'a>,
+    skip_frame: AVDiscard,
+    done: bool,
+}
+
+impl<'s, 'a> Iterator for Keyframes<'s, 'a> {
+    type Item = Result<Frame, FFmpegError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.stream.next_video_frame() {
+            Ok(frame) => Some(Ok(frame)),
+            Err(ref err) if err.kind() == ErrorKind::Eof => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+impl<'s, 'a> Drop for Keyframes<'s, 'a> {
+    fn drop(&mut self) {
+        self.stream.decode_ctx.key_only = 0;
+        self.stream.decode_ctx.skip_frame = self.skip_frame;
+        unsafe { wrapper_apply_skip_options(&mut self.stream.decode_ctx) };
+    }
+}
+
+pub struct FramesAt<'s, 'a> {
+    stream: &'s mut Stream<'a>,
+    targets: Vec<i64>,
+    order: Vec<usize>,
+    results: Vec<Option<Result<Frame, FFmpegError>>>,
+    next_target: usize,
+    next_result: usize,
+    last: Option<(i64, usize)>,
+}
+
+impl<'s, 'a> FramesAt<'s, 'a> {
+    fn decode_next_target(&mut self) {
+        let i = self.order[self.next_target];
+        self.next_target += 1;
+        let target = self.targets[i];
+
+        // the previous target already landed on a frame at or after this one
+        if let Some((pts, j)) = self.last {
+            if pts >= target {
+                if let Some(Ok(frame)) = &self.results[j] {
+                    self.results[i] = Some(Ok(frame.clone()));
+                    return;
+                }
+            }
+        }
+
+        // keep decoding when the target is in the current GOP, otherwise seek to its keyframe
+        let stream = &mut *self.stream;
+        let forward = match self.last {
+            Some((pts, _)) if pts < target => stream
+                .keyframe_before_pts(target)
+                .is_some_and(|key| key <= pts),
+            _ => false,
+        };
+        if forward || unsafe { !(*stream.format).is_seekable() } {
+            stream.pending_seek.set(Some(target));
+        } else if let Err(err) = stream.seek_to_pts(target, "ffmpeg_frames_at") {
+            self.last = None;
+            self.results[i] = Some(Err(err));
+            return;
+        }
+
+        let result = stream.next_video_frame();
+        self.last = match &result {
+            Ok(frame) => frame.meta.pts.map(|pts| (pts, i)),
+            Err(_) => None,
+        };
+        self.results[i] = Some(result);
+    }
+}
+
+impl<'s, 'a> Iterator for FramesAt<'s, 'a> {
+    type Item = Result<Frame, FFmpegError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next_result >= self.results.len() {
+            return None;
+        }
+        while self.results[self.next_result].is_none() {
+            self.decode_next_target();
+        }
+        self.next_result += 1;
+        self.results[self.next_result - 1].take()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub tick: u64,
+    pub time: f64,
+    pub source_time: f64,
+    pub frame: Frame,
+}
+
+// a reference to a decoded frame kept alive across later decode calls
+struct HeldFrame {
+    time: f64,
+    frame: *mut AVFrame,
+}
+
+impl Drop for HeldFrame {
+    fn drop(&mut self) {
+        unsafe { avformat::av_frame_free(&mut self.frame) };
+    }
+}
+
+pub struct Samples<'s, 'a> {
+    stream: &'s mut Stream<'a>,
+    rate: f64,
+    mode: SampleMode,
+    tick: u64,
+    started: bool,
+    prev: Option<HeldFrame>,
+    next: Option<HeldFrame>,
+    eof: bool,
+    done: bool,
+}
+
+impl<'s, 'a> Samples<'s, 'a> {
+    fn sample(&mut self, time: f64, frame: *mut AVFrame) -> Result<Sample, FFmpegError> {
+        let frame = match self.stream.convert_frame(frame) {
+            Ok(frame) => frame,
+            Err(err) => {
+                self.done = true;
+                return Err(err);
+            }
+        };
+        let sample = Sample {
+            tick: self.tick,
+            time: self.tick as f64 / self.rate,
+            source_time: time,
+            frame,
+        };
+        self.tick += 1;
+        Ok(sample)
+    }
+
+    fn frame_end(&self, held: &HeldFrame) -> f64 {
+        let tb = unsafe { (*self.stream.handle).time_base };
+        match unsafe { (*held.frame).pkt_duration } {
+            d if d > 0 => held.time + d as f64 * tb.num as f64 / tb.den as f64,
+            _ if self.stream.fps > 0.0 => held.time + 1.0 / self.stream.fps as f64,
+            _ => held.time,
+        }
+    }
+}
+
+impl<'s, 'a> Iterator for Samples<'s, 'a> {
+    type Item = Result<Sample, FFmpegError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.done {
+                return None;
+            }
+            let t = self.tick as f64 / self.rate;
+
+            if let Some(next) = self.next.as_ref() {
+                if t < next.time {
+                    let chosen = match (self.mode, self.prev.as_ref()) {
+                        (SampleMode::AtOrBefore, Some(prev)) => prev,
+                        (SampleMode::Nearest, Some(prev)) if t - prev.time <= next.time - t => prev,
+                        (SampleMode::AtOrBefore, None) => {
+                            self.tick += 1;
+                            continue;
+                        }
+                        _ => next,
+                    };
+                    let (time, frame) = (chosen.time, chosen.frame);
+                    return Some(self.sample(time, frame));
+                }
+                self.prev = self.next.take();
+            }
+
+            if self.eof {
+                // the last frame covers the ticks up to its end
+                let (time, frame, end) = match self.prev.as_ref() {
+                    Some(prev) => (prev.time, prev.frame, self.frame_end(prev)),
+                    None => {
+                        self.done = true;
+                        continue;
+                    }
+                };
+                if t > time && t >= end {
+                    self.done = true;
+                    continue;
+                }
+                return Some(self.sample(time, frame));
+            }
+
+            // only pick frames by pts here, conversion happens for the chosen ones
+            let held = match self.stream.next_frame_ref() {
+                Ok(frame) => frame
+                    .meta()
+                    .pts
+                    .map(|pts| (pts, unsafe { avformat::av_frame_clone(frame.frame) })),
+                Err(ref err) if err.kind() == ErrorKind::Eof => {
+                    self.eof = true;
+                    continue;
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            };
+            let (pts, frame) = match held {
+                Some((_, frame)) if frame.is_null() => {
+                    self.done = true;
+                    return Some(Err(FFmpegError::new(-(ENOMEM as i32), "next_frame")));
+                }
+                Some(held) => held,
+                None => continue,
+            };
+            let time = self.stream.pts_to_time(pts);
+            if !self.started {
+                // align to the tick grid of the stream start, not to the first frame; a
+                // nearest sample may take the tick just before the frame
+                let tick = match self.mode {
+                    SampleMode::Nearest => (time * self.rate).round(),
+                    SampleMode::AtOrBefore => (time * self.rate).ceil(),
+                };
+                self.tick = tick.max(0.0) as u64;
+                self.started = true;
+            }
+            self.next = Some(HeldFrame { time, frame });
+        }
+    }
+}
+
+fn output_planes(output: &WrapperFrameOutput) -> Vec<Plane> {
+    let base = output.dst_buf[0] as usize;
+    let log2_chroma_h = unsafe { (*imgutils::av_pix_fmt_desc_get(output.pix_fmt)).log2_chroma_h };
+    output
+        .dst_buf
+        .iter()
+        .zip(output.dst_linesize.iter())
+        .take_while(|(p, l)| !p.is_null() && **l > 0)
+        .enumerate()
+        .map(|(i, (p, l))| Plane {
+            offset: *p as usize - base,
+            stride: *l,
+            height: if i == 1 || i == 2 {
+                -((-output.height) >> log2_chroma_h)
+            } else {
+                output.height
+            },
+        })
+        .collect()
+}
+
+fn degree_to_orientation(deg: i32, mirrored: bool) -> Orientation {
+    let quarter = ((deg as f32 / 90.0).round() as i32).rem_euclid(4);
+    match (quarter, mirrored) {
+        (1, false) => Orientation::Left,
+        (2, false) => Orientation::Bottom,
+        (3, false) => Orientation::Right,
+        (_, false) => Orientation::Top,
+        (1, true) => Orientation::LeftMirrored,
+        (2, true) => Orientation::BottomMirrored,
+        (3, true) => Orientation::RightMirrored,
+        (_, true) => Orientation::TopMirrored,
     }
 }
@@ -1,13 +1,24 @@
 #![allow(non_snake_case)]
 
+use crate::binding::avcodec::{AVERROR_EOF, AVERROR_EXIT};
 use crate::binding::avformat::{
-    self, AVFormatContext, AVIOContext, AVMediaType_AVMEDIA_TYPE_AUDIO,
-    AVMediaType_AVMEDIA_TYPE_VIDEO, AVStream, AVSEEK_SIZE, SEEK_CUR, SEEK_END, SEEK_SET,
+    self, AVDictionary, AVDictionaryEntry, AVFormatContext, AVIOContext, AVIOInterruptCB,
+    AVInputFormat, AVMediaType_AVMEDIA_TYPE_AUDIO, AVMediaType_AVMEDIA_TYPE_VIDEO, AVProbeData,
+    AVStream, AVPROBE_PADDING_SIZE, AVSEEK_FORCE, AVSEEK_SIZE, AV_DICT_IGNORE_SUFFIX, EIO,
+    SEEK_CUR, SEEK_END, SEEK_SET,
 };
+use std::ffi::{CStr, CString};
+use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::os::raw::{c_char, c_int, c_void};
+use std::panic::{self, AssertUnwindSafe};
 use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
 
 pub const WRAPPER_BUFFER_SIZE: i32 = 32768;
+pub const WRAPPER_INTERRUPT_CANCELLED: c_int = 1;
+pub const WRAPPER_INTERRUPT_TIMED_OUT: c_int = 2;
 
 #[derive(Debug)]
 pub struct WrapperAvioBuffer {
@@ -15,6 +26,38 @@ pub struct WrapperAvioBuffer {
     pub ptr: *mut u8,
     pub total: i64,
     pub size: i64,
+    pub interrupt: *mut WrapperInterrupt,
+}
+
+pub trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+pub enum WrapperReaderInner {
+    Seekable(Box<dyn ReadSeek>),
+    Forward(Box<dyn Read + Send>),
+}
+
+pub struct WrapperReader {
+    pub inner: WrapperReaderInner,
+    pub error: Option<String>,
+    pub interrupt: *mut WrapperInterrupt,
+}
+
+pub trait WriteSeek: Write + Seek {}
+
+impl<T: Write + Seek> WriteSeek for T {}
+
+pub struct WrapperWriter<'w> {
+    pub inner: &'w mut dyn WriteSeek,
+    pub error: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct WrapperInterrupt {
+    pub cancelled: Option<Arc<AtomicBool>>,
+    pub deadline: Option<Instant>,
+    pub reason: c_int,
 }
 
 #[derive(Debug)]
@@ -27,6 +70,11 @@ pub struct WrapperInputParams {
     pub ioctx: *mut AVIOContext,
     pub ioctx_buffer: *mut u8,
     pub iobuffer: *mut WrapperAvioBuffer,
+    pub reader: *mut WrapperReader,
+    pub input_format: *mut AVInputFormat,
+    pub interrupt: *mut WrapperInterrupt,
+    pub fmt_opts: *mut AVDictionary,
+    pub info_opts: *mut AVDictionary,
 }
 
 fn ffmin(a: c_int, b: c_int) -> c_int {
@@ -36,8 +84,16 @@ fn ffmin(a: c_int, b: c_int) -> c_int {
     a
 }
 
+// custom AVIOContexts never consult fmtctx.interrupt_callback, so the callbacks check it
+unsafe fn wrapper_interrupted(it: *mut WrapperInterrupt) -> bool {
+    !it.is_null() && interrupt_cb(it as *mut c_void) != 0
+}
+
 pub unsafe extern "C" fn read_packet(opaque: *mut c_void, buf: *mut u8, buf_size: c_int) -> c_int {
     let bd: *mut WrapperAvioBuffer = opaque as (*mut WrapperAvioBuffer);
+    if wrapper_interrupted((*bd).interrupt) {
+        return AVERROR_EXIT;
+    }
     let new_buf_size = ffmin(buf_size, (*bd).size as i32);
     avformat::memcpy(
         buf as (*mut c_void),
@@ -52,6 +108,9 @@ pub unsafe extern "C" fn read_packet(opaque: *mut c_void, buf: *mut u8, buf_size
 pub unsafe extern "C" fn seek_packet(opaque: *mut c_void, offset: i64, whence: i32) -> i64 {
     let bd: *mut WrapperAvioBuffer = opaque as (*mut WrapperAvioBuffer);
     let new_size: i64;
+    if wrapper_interrupted((*bd).interrupt) {
+        return AVERROR_EXIT as i64;
+    }
 
     match whence as u32 {
         SEEK_SET => {
@@ -83,48 +142,244 @@ pub unsafe extern "C" fn seek_packet(opaque: *mut c_void, offset: i64, whence: i
     return -1;
 }
 
-pub unsafe fn wrapper_fillin_buffer(data: *const u8, size: i64) -> *mut WrapperAvioBuffer {
+fn panic_message(who: &str, payload: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        format!("{} panicked: {}", who, s)
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        format!("{} panicked: {}", who, s)
+    } else {
+        format!("{} panicked", who)
+    }
+}
+
+pub unsafe extern "C" fn read_reader(opaque: *mut c_void, buf: *mut u8, buf_size: c_int) -> c_int {
+    let rd: &mut WrapperReader = &mut *(opaque as *mut WrapperReader);
+    if rd.error.is_some() {
+        return -(EIO as c_int);
+    }
+    if wrapper_interrupted(rd.interrupt) {
+        return AVERROR_EXIT;
+    }
+
+    let out = std::slice::from_raw_parts_mut(buf, buf_size as usize);
+    let ret = loop {
+        let inner = &mut rd.inner;
+        let read = || match inner {
+            WrapperReaderInner::Seekable(r) => r.read(out),
+            WrapperReaderInner::Forward(r) => r.read(out),
+        };
+        match panic::catch_unwind(AssertUnwindSafe(read)) {
+            Ok(Err(ref e)) if e.kind() == io::ErrorKind::Interrupted => continue,
+            ret => break ret,
+        }
+    };
+    match ret {
+        Ok(Ok(0)) => AVERROR_EOF,
+        Ok(Ok(n)) => n as c_int,
+        Ok(Err(e)) => {
+            rd.error = Some(e.to_string());
+            -(EIO as c_int)
+        }
+        Err(payload) => {
+            rd.error = Some(panic_message("reader", payload));
+            -(EIO as c_int)
+        }
+    }
+}
+
+fn reader_size(inner: &mut dyn ReadSeek) -> io::Result<u64> {
+    let current = inner.stream_position()?;
+    let size = inner.seek(SeekFrom::End(0))?;
+    inner.seek(SeekFrom::Start(current))?;
+    Ok(size)
+}
+
+pub unsafe extern "C" fn seek_reader(opaque: *mut c_void, offset: i64, whence: c_int) -> i64 {
+    let rd: &mut WrapperReader = &mut *(opaque as *mut WrapperReader);
+    if rd.error.is_some() {
+        return -(EIO as i64);
+    }
+    if wrapper_interrupted(rd.interrupt) {
+        return AVERROR_EXIT as i64;
+    }
+    let inner = match rd.inner {
+        WrapperReaderInner::Seekable(ref mut r) => &mut **r,
+        WrapperReaderInner::Forward(_) => return -1,
+    };
+
+    let pos = match whence as u32 & !AVSEEK_FORCE {
+        SEEK_SET if offset >= 0 => SeekFrom::Start(offset as u64),
+        SEEK_CUR => SeekFrom::Current(offset),
+        SEEK_END => SeekFrom::End(offset),
+        AVSEEK_SIZE => {
+            return match panic::catch_unwind(AssertUnwindSafe(|| reader_size(inner))) {
+                Ok(Ok(size)) => size as i64,
+                // Unknown size is not fatal, the demuxer falls back to reading.
+                Ok(Err(_)) => -1,
+                Err(payload) => {
+                    rd.error = Some(panic_message("reader", payload));
+                    -(EIO as i64)
+                }
+            };
+        }
+        _ => return -1,
+    };
+
+    match panic::catch_unwind(AssertUnwindSafe(|| inner.seek(pos))) {
+        Ok(Ok(n)) => n as i64,
+        Ok(Err(e)) => {
+            rd.error = Some(e.to_string());
+            -(EIO as i64)
+        }
+        Err(payload) => {
+            rd.error = Some(panic_message("reader", payload));
+            -(EIO as i64)
+        }
+    }
+}
+
+pub unsafe extern "C" fn write_writer(opaque: *mut c_void, buf: *mut u8, buf_size: c_int) -> c_int {
+    let wr: &mut WrapperWriter = &mut *(opaque as *mut WrapperWriter);
+    if wr.error.is_some() {
+        return -(EIO as c_int);
+    }
+
+    let data = std::slice::from_raw_parts(buf, buf_size as usize);
+    let inner = &mut *wr.inner;
+    match panic::catch_unwind(AssertUnwindSafe(|| inner.write_all(data))) {
+        Ok(Ok(())) => buf_size,
+        Ok(Err(e)) => {
+            wr.error = Some(e.to_string());
+            -(EIO as c_int)
+        }
+        Err(payload) => {
+            wr.error = Some(panic_message("writer", payload));
+            -(EIO as c_int)
+        }
+    }
+}
+
+pub unsafe extern "C" fn seek_writer(opaque: *mut c_void, offset: i64, whence: c_int) -> i64 {
+    let wr: &mut WrapperWriter = &mut *(opaque as *mut WrapperWriter);
+    if wr.error.is_some() {
+        return -(EIO as i64);
+    }
+
+    let pos = match whence as u32 & !AVSEEK_FORCE {
+        SEEK_SET if offset >= 0 => SeekFrom::Start(offset as u64),
+        SEEK_CUR => SeekFrom::Current(offset),
+        SEEK_END => SeekFrom::End(offset),
+        _ => return -1,
+    };
+
+    let inner = &mut *wr.inner;
+    match panic::catch_unwind(AssertUnwindSafe(|| inner.seek(pos))) {
+        Ok(Ok(n)) => n as i64,
+        Ok(Err(e)) => {
+            wr.error = Some(e.to_string());
+            -(EIO as i64)
+        }
+        Err(payload) => {
+            wr.error = Some(panic_message("writer", payload));
+            -(EIO as i64)
+        }
+    }
+}
+
+pub unsafe extern "C" fn interrupt_cb(opaque: *mut c_void) -> c_int {
+    let it: &mut WrapperInterrupt = &mut *(opaque as *mut WrapperInterrupt);
+    if it.reason == 0 {
+        if it
+            .cancelled
+            .as_ref()
+            .is_some_and(|c| c.load(Ordering::SeqCst))
+        {
+            it.reason = WRAPPER_INTERRUPT_CANCELLED;
+        } else if it.deadline.is_some_and(|d| Instant::now() >= d) {
+            it.reason = WRAPPER_INTERRUPT_TIMED_OUT;
+        }
+    }
+    (it.reason != 0) as c_int
+}
+
+pub unsafe fn wrapper_borrow_buffer(data: *const u8, size: i64) -> *mut WrapperAvioBuffer {
     let buffer: *mut WrapperAvioBuffer =
         avformat::malloc(std::mem::size_of::<WrapperAvioBuffer>()) as (*mut WrapperAvioBuffer);
-    (*buffer).base = avformat::malloc(size as usize) as (*mut u8);
+    (*buffer).base = data as *mut u8;
     (*buffer).ptr = (*buffer).base;
     (*buffer).size = size;
     (*buffer).total = size;
-    avformat::memcpy(
-        (*buffer).ptr as (*mut c_void),
-        data as (*mut c_void),
-        size as usize,
-    );
+    (*buffer).interrupt = ptr::null_mut();
     buffer
 }
 
 pub unsafe fn wrapper_avformat_open_input(fp: *const c_char, p: *mut WrapperInputParams) -> i32 {
-    if fp.is_null() {
-        (*p).fmtctx = avformat::avformat_alloc_context();
-        if (*p).fmtctx.is_null() {
-            return -888;
+    (*p).fmtctx = avformat::avformat_alloc_context();
+    if (*p).fmtctx.is_null() {
+        return -888;
+    }
+
+    if !(*p).interrupt.is_null() {
+        (*(*p).fmtctx).interrupt_callback = AVIOInterruptCB {
+            callback: Some(interrupt_cb),
+            opaque: (*p).interrupt as *mut c_void,
+        };
+        if !(*p).iobuffer.is_null() {
+            (*(*p).iobuffer).interrupt = (*p).interrupt;
         }
+        if !(*p).reader.is_null() {
+            (*(*p).reader).interrupt = (*p).interrupt;
+        }
+    }
 
+    if fp.is_null() {
         (*p).ioctx_buffer = avformat::av_malloc(WRAPPER_BUFFER_SIZE as usize) as (*mut u8);
-        (*p).ioctx = avformat::avio_alloc_context(
-            (*p).ioctx_buffer,
-            WRAPPER_BUFFER_SIZE,
-            0,
-            (*p).iobuffer as *mut c_void,
-            Some(read_packet),
-            None,
-            Some(seek_packet),
-        );
+        (*p).ioctx = if (*p).reader.is_null() {
+            avformat::avio_alloc_context(
+                (*p).ioctx_buffer,
+                WRAPPER_BUFFER_SIZE,
+                0,
+                (*p).iobuffer as *mut c_void,
+                Some(read_packet),
+                None,
+                Some(seek_packet),
+            )
+        } else if let WrapperReaderInner::Forward(_) = (*(*p).reader).inner {
+            let ioctx = avformat::avio_alloc_context(
+                (*p).ioctx_buffer,
+                WRAPPER_BUFFER_SIZE,
+                0,
+                (*p).reader as *mut c_void,
+                Some(read_reader),
+                None,
+                None,
+            );
+            if !ioctx.is_null() {
+                (*ioctx).seekable = 0;
+            }
+            ioctx
+        } else {
+            avformat::avio_alloc_context(
+                (*p).ioctx_buffer,
+                WRAPPER_BUFFER_SIZE,
+                0,
+                (*p).reader as *mut c_void,
+                Some(read_reader),
+                None,
+                Some(seek_reader),
+            )
+        };
         (*(*p).fmtctx).pb = (*p).ioctx;
     }
 
     let mut ret: i32;
-    ret = avformat::avformat_open_input(&mut (*p).fmtctx, fp, ptr::null_mut(), ptr::null_mut());
+    ret =
+        avformat::avformat_open_input(&mut (*p).fmtctx, fp, (*p).input_format, &mut (*p).fmt_opts);
     if ret < 0 {
         return ret;
     }
 
-    ret = avformat::avformat_find_stream_info((*p).fmtctx, ptr::null_mut());
+    ret = wrapper_find_stream_info((*p).fmtctx, &mut (*p).info_opts);
     if ret != 0 {
         return ret;
     }
@@ -147,6 +402,77 @@ pub unsafe fn wrapper_avformat_open_input(fp: *const c_char, p: *mut WrapperInpu
     return 0;
 }
 
+unsafe fn wrapper_find_stream_info(
+    fmtctx: *mut AVFormatContext,
+    opts: *mut *mut AVDictionary,
+) -> i32 {
+    if (*opts).is_null() {
+        return avformat::avformat_find_stream_info(fmtctx, ptr::null_mut());
+    }
+
+    let n = (*fmtctx).nb_streams as usize;
+    let mut stream_opts = vec![ptr::null_mut::<AVDictionary>(); n];
+    for d in stream_opts.iter_mut() {
+        avformat::av_dict_copy(d, *opts, 0);
+    }
+
+    let ret = avformat::avformat_find_stream_info(fmtctx, stream_opts.as_mut_ptr());
+
+    // An option counts as consumed as soon as one of the stream decoders accepted it.
+    for key in wrapper_dict_keys(*opts) {
+        let c_key = CString::new(key).unwrap();
+        let unused = stream_opts
+            .iter()
+            .all(|d| !avformat::av_dict_get(*d, c_key.as_ptr(), ptr::null(), 0).is_null());
+        if n > 0 && !unused {
+            avformat::av_dict_set(opts, c_key.as_ptr(), ptr::null(), 0);
+        }
+    }
+    for d in stream_opts.iter_mut() {
+        avformat::av_dict_free(d);
+    }
+    ret
+}
+
+pub unsafe fn wrapper_dict_from_pairs(pairs: &[(String, String)]) -> *mut AVDictionary {
+    let mut dict = ptr::null_mut();
+    for (key, value) in pairs {
+        let c_key = CString::new(key.as_str()).unwrap();
+        let c_value = CString::new(value.as_str()).unwrap();
+        avformat::av_dict_set(&mut dict, c_key.as_ptr(), c_value.as_ptr(), 0);
+    }
+    dict
+}
+
+pub unsafe fn wrapper_dict_keys(dict: *const AVDictionary) -> Vec<String> {
+    let mut keys = Vec::new();
+    let mut entry = ptr::null_mut::<AVDictionaryEntry>();
+    let empty = CString::new("").unwrap();
+    loop {
+        entry = avformat::av_dict_get(dict, empty.as_ptr(), entry, AV_DICT_IGNORE_SUFFIX as i32);
+        if entry.is_null() {
+            break;
+        }
+        keys.push(CStr::from_ptr((*entry).key).to_string_lossy().into_owned());
+    }
+    keys
+}
+
+pub unsafe fn wrapper_probe_input(data: &[u8], score: *mut c_int) -> *mut AVInputFormat {
+    let mut buf = Vec::with_capacity(data.len() + AVPROBE_PADDING_SIZE as usize);
+    buf.extend_from_slice(data);
+    buf.resize(data.len() + AVPROBE_PADDING_SIZE as usize, 0);
+
+    let filename = CString::new("").unwrap();
+    let mut pd = AVProbeData {
+        filename: filename.as_ptr(),
+        buf: buf.as_mut_ptr(),
+        buf_size: data.len() as c_int,
+        mime_type: ptr::null(),
+    };
+    avformat::av_probe_input_format3(&mut pd, 1, score)
+}
+
 pub unsafe fn wrapper_get_stream_handlers(
     fmtctx: *mut AVFormatContext,
     codec_type: i32,
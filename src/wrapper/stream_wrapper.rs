@@ -3,22 +3,44 @@ use std::os::raw::{c_int, c_void};
 use std::ptr;
 
 use crate::binding::audio_fifo::{self, AVAudioFifo};
-use crate::binding::avcodec::{AVERROR_EOF, EAGAIN};
+use crate::binding::avcodec::{AVERROR_EOF, EAGAIN, EINVAL, ENOBUFS};
 use crate::binding::avformat::{
-    self, AVCodec, AVCodecContext, AVCodecParameters, AVDictionaryEntry, AVFormatContext, AVFrame,
-    AVMediaType_AVMEDIA_TYPE_VIDEO, AVPacket, AVPixelFormat_AV_PIX_FMT_BGR24, AVRational,
-    AVRounding_AV_ROUND_UP, AVStream, AVSEEK_FLAG_ANY,
+    self, AVCodec, AVCodecContext, AVCodecParameters, AVDictionaryEntry, AVDiscard,
+    AVFormatContext, AVFrame, AVMediaType_AVMEDIA_TYPE_VIDEO, AVPacket,
+    AVPacketSideDataType_AV_PKT_DATA_DISPLAYMATRIX, AVPixelFormat, AVRational,
+    AVRounding_AV_ROUND_UP, AVStream, AVSEEK_FLAG_BACKWARD, AV_PKT_FLAG_KEY,
 };
 use crate::binding::avutil::AV_NOPTS_VALUE;
 use crate::binding::imgutils;
 use crate::binding::swresample::{self, AVSampleFormat_AV_SAMPLE_FMT_S16, SwrContext};
-use crate::binding::swscale::{self, SwsContext, SWS_FAST_BILINEAR};
+use crate::binding::swscale::{self, SwsContext};
+
+pub const WRAPPER_RESIZE_NONE: c_int = 0;
+pub const WRAPPER_RESIZE_EXACT: c_int = 1;
+pub const WRAPPER_RESIZE_FIT: c_int = 2;
+pub const WRAPPER_RESIZE_SCALE: c_int = 3;
 
 #[derive(Debug)]
 pub struct WrapperFrameOutput {
-    pub bgr24_buf: [*mut u8; 4usize],
+    pub pix_fmt: AVPixelFormat,
+    pub sws_flags: c_int,
+    pub resize_mode: c_int,
+    pub resize_width: c_int,
+    pub resize_height: c_int,
+    pub resize_factor: f32,
+    pub rotation: c_int,
+    pub mirror: c_int,
+    pub rotate_buf: [*mut u8; 4usize],
+    pub rotate_linesize: [c_int; 4usize],
+    pub image_buf: [*mut u8; 4usize],
     pub linesize: [c_int; 4usize],
+    pub dst_buf: [*mut u8; 4usize],
+    pub dst_linesize: [c_int; 4usize],
     pub img_convert_ctx: *mut SwsContext,
+    pub src_width: c_int,
+    pub src_height: c_int,
+    pub src_format: AVPixelFormat,
+    pub changed: c_int,
     pub buffer: *mut u8,
     pub allocated: c_int,
     pub buffersize: c_int,
@@ -32,6 +54,7 @@ pub struct WrapperStreamMeta {
     pub fps: f32,
     pub idx: c_int,
     pub rtt: c_int,
+    pub mirror: c_int,
     pub nfr: c_int,
     pub dur: f32,
     pub tb: f32,
@@ -43,6 +66,17 @@ pub struct WrapperDecodeCtx {
     pub frame: *mut AVFrame,
     pub packet: *mut AVPacket,
     pub output: WrapperFrameOutput,
+    pub thread_count: c_int,
+    pub thread_type: c_int,
+    pub skip_frame: AVDiscard,
+    pub skip_loop_filter: AVDiscard,
+    pub skip_idct: AVDiscard,
+    pub lowres: c_int,
+    pub key_only: c_int,
+    pub seek_target: i64,
+    pub last_pts: i64,
+    pub draining: c_int,
+    pub held: c_int,
     pub need_sent: c_int,
     pub pts: f32,
     pub dts: f32,
@@ -80,64 +114,268 @@ pub unsafe extern "C" fn next_packet_for_stream(
     return ret;
 }
 
-pub unsafe fn frame_to_rawdata_rgb(
+pub unsafe fn wrapper_output_size(
+    output: *const WrapperFrameOutput,
+    width: c_int,
+    height: c_int,
+    dst_width: *mut c_int,
+    dst_height: *mut c_int,
+) {
+    let (w, h) = match (*output).resize_mode {
+        WRAPPER_RESIZE_EXACT => ((*output).resize_width, (*output).resize_height),
+        WRAPPER_RESIZE_FIT => {
+            let sx = (*output).resize_width as f64 / width as f64;
+            let sy = (*output).resize_height as f64 / height as f64;
+            let s = sx.min(sy);
+            (
+                (width as f64 * s).round() as c_int,
+                (height as f64 * s).round() as c_int,
+            )
+        }
+        WRAPPER_RESIZE_SCALE => {
+            let s = (*output).resize_factor as f64;
+            (
+                (width as f64 * s).round() as c_int,
+                (height as f64 * s).round() as c_int,
+            )
+        }
+        _ => (width, height),
+    };
+    *dst_width = w.max(1);
+    *dst_height = h.max(1);
+}
+
+#[inline]
+fn ceil_rshift(a: c_int, b: u8) -> c_int {
+    -((-a) >> b)
+}
+
+pub unsafe fn rotate_image(
+    output: *mut WrapperFrameOutput,
+    dst_data: *const *mut u8,
+    dst_linesize: *const c_int,
+    width: c_int,
+    height: c_int,
+) {
+    let desc = imgutils::av_pix_fmt_desc_get((*output).pix_fmt);
+    let rotation = (*output).rotation;
+    let mirror = (*output).mirror != 0;
+
+    for i in 0..4 {
+        let src = (*output).rotate_buf[i];
+        let src_linesize = (*output).rotate_linesize[i];
+        if src.is_null() || src_linesize <= 0 {
+            break;
+        }
+        let (pw, ph) = if i == 1 || i == 2 {
+            (
+                ceil_rshift(width, (*desc).log2_chroma_w),
+                ceil_rshift(height, (*desc).log2_chroma_h),
+            )
+        } else {
+            (width, height)
+        };
+        let elem = (src_linesize / pw) as usize;
+        let dst = *dst_data.add(i);
+        let dst_linesize = *dst_linesize.add(i) as usize;
+        let (pw, ph) = (pw as usize, ph as usize);
+
+        for y in 0..ph {
+            let row = src.add(y * src_linesize as usize);
+            for x in 0..pw {
+                let (dx, dy) = match rotation {
+                    90 => (ph - 1 - y, x),
+                    180 => (pw - 1 - x, ph - 1 - y),
+                    270 => (y, pw - 1 - x),
+                    _ => (x, y),
+                };
+                let dx = match (mirror, rotation) {
+                    (false, _) => dx,
+                    (true, 90) | (true, 270) => ph - 1 - dx,
+                    (true, _) => pw - 1 - dx,
+                };
+                ptr::copy_nonoverlapping(
+                    row.add(x * elem),
+                    dst.add(dy * dst_linesize + dx * elem),
+                    elem,
+                );
+            }
+        }
+    }
+}
+
+pub unsafe fn wrapper_output_image_size(
+    output: *const WrapperFrameOutput,
+    width: c_int,
+    height: c_int,
+    out_width: *mut c_int,
+    out_height: *mut c_int,
+) -> c_int {
+    let mut dst_width = 0;
+    let mut dst_height = 0;
+    wrapper_output_size(output, width, height, &mut dst_width, &mut dst_height);
+    match (*output).rotation {
+        90 | 270 => {
+            *out_width = dst_height;
+            *out_height = dst_width;
+        }
+        _ => {
+            *out_width = dst_width;
+            *out_height = dst_height;
+        }
+    }
+    imgutils::av_image_get_buffer_size((*output).pix_fmt, *out_width, *out_height, 1)
+}
+
+pub unsafe fn frame_to_rawdata(
     avctx: *mut AVCodecContext,
     from: *mut AVFrame,
     output: *mut WrapperFrameOutput,
 ) -> i32 {
-    let width = (*avctx).width;
-    let height = (*avctx).height;
-    let pixel_format = (*avctx).pix_fmt;
+    frame_to_buffer(avctx, from, output, ptr::null_mut(), 0)
+}
+
+pub unsafe fn frame_to_buffer(
+    avctx: *mut AVCodecContext,
+    from: *mut AVFrame,
+    output: *mut WrapperFrameOutput,
+    dst: *mut u8,
+    dst_size: c_int,
+) -> i32 {
+    let (width, height, pixel_format) = if (*from).width > 0 && (*from).height > 0 {
+        ((*from).width, (*from).height, (*from).format)
+    } else {
+        ((*avctx).width, (*avctx).height, (*avctx).pix_fmt)
+    };
+
+    if !(*output).img_convert_ctx.is_null()
+        && ((*output).src_width != width
+            || (*output).src_height != height
+            || (*output).src_format != pixel_format)
+    {
+        wrapper_reset_output(output);
+        (*output).changed = 1;
+    }
+    let mut dst_width = 0;
+    let mut dst_height = 0;
+    wrapper_output_size(output, width, height, &mut dst_width, &mut dst_height);
+    let mut out_width = 0;
+    let mut out_height = 0;
+    let size = wrapper_output_image_size(output, width, height, &mut out_width, &mut out_height);
+    if size < 0 {
+        return size;
+    }
+    let transform = (*output).rotation != 0 || (*output).mirror != 0;
 
     if (*output).img_convert_ctx.is_null() {
         (*output).img_convert_ctx = swscale::sws_getContext(
             width,
             height,
             pixel_format,
-            width,
-            height,
-            AVPixelFormat_AV_PIX_FMT_BGR24,
-            SWS_FAST_BILINEAR as i32,
+            dst_width,
+            dst_height,
+            (*output).pix_fmt,
+            (*output).sws_flags,
             ptr::null_mut(),
             ptr::null_mut(),
             ptr::null_mut(),
-        )
+        );
+        if (*output).img_convert_ctx.is_null() {
+            return -(EINVAL as i32);
+        }
+        (*output).src_width = width;
+        (*output).src_height = height;
+        (*output).src_format = pixel_format;
     }
 
     let mut ret: i32;
-    if (*output).allocated == 0 {
+    if dst.is_null() {
+        if (*output).allocated == 0 {
+            ret = imgutils::av_image_alloc(
+                (*output).image_buf.as_mut_ptr(),
+                (*output).linesize.as_mut_ptr(),
+                out_width,
+                out_height,
+                (*output).pix_fmt,
+                1,
+            );
+            if ret < 0 {
+                return ret;
+            }
+            (*output).allocated = 1;
+        }
+        (*output).dst_buf = (*output).image_buf;
+        (*output).dst_linesize = (*output).linesize;
+    } else {
+        if dst_size < size {
+            return -(ENOBUFS as i32);
+        }
+        ret = imgutils::av_image_fill_arrays(
+            (*output).dst_buf.as_mut_ptr(),
+            (*output).dst_linesize.as_mut_ptr(),
+            dst,
+            (*output).pix_fmt,
+            out_width,
+            out_height,
+            1,
+        );
+        if ret < 0 {
+            return ret;
+        }
+        for i in 0..4 {
+            if (*output).dst_linesize[i] == 0 {
+                (*output).dst_buf[i] = ptr::null_mut();
+            }
+        }
+    }
+
+    if transform && (*output).rotate_buf[0].is_null() {
         ret = imgutils::av_image_alloc(
-            (*output).bgr24_buf.as_mut_ptr(),
-            (*output).linesize.as_mut_ptr(),
-            width,
-            height,
-            AVPixelFormat_AV_PIX_FMT_BGR24,
+            (*output).rotate_buf.as_mut_ptr(),
+            (*output).rotate_linesize.as_mut_ptr(),
+            dst_width,
+            dst_height,
+            (*output).pix_fmt,
             1,
         );
         if ret < 0 {
             return ret;
         }
-        (*output).allocated = 1;
     }
 
+    let (scale_buf, scale_linesize) = if transform {
+        (&mut (*output).rotate_buf, &mut (*output).rotate_linesize)
+    } else {
+        (&mut (*output).dst_buf, &mut (*output).dst_linesize)
+    };
     ret = swscale::sws_scale(
         (*output).img_convert_ctx,
         (*from).data.as_ptr() as (*const *const u8),
         (*from).linesize.as_ptr(),
         0,
         height,
-        (*output).bgr24_buf.as_mut_ptr(),
-        (*output).linesize.as_mut_ptr(),
+        scale_buf.as_mut_ptr(),
+        scale_linesize.as_mut_ptr(),
     );
     if ret < 0 {
         return ret;
     }
+    if transform {
+        rotate_image(
+            output,
+            (*output).dst_buf.as_ptr(),
+            (*output).dst_linesize.as_ptr(),
+            dst_width,
+            dst_height,
+        );
+    }
 
-    let stride = (*output).linesize[0];
+    let stride = (*output).dst_linesize[0];
 
-    (*output).buffer = (*output).bgr24_buf[0];
-    (*output).height = height;
-    (*output).width = width;
+    (*output).buffer = (*output).dst_buf[0];
+    (*output).buffersize = size;
+    (*output).height = out_height;
+    (*output).width = out_width;
     (*output).stride = stride;
 
     return 0;
@@ -145,6 +383,8 @@ pub unsafe fn frame_to_rawdata_rgb(
 
 pub unsafe fn wrapper_get_meta(s: *mut AVStream, kind: i32, out: *mut WrapperStreamMeta) {
     (*out).idx = (*s).index;
+    (*out).rtt = 0;
+    (*out).mirror = 0;
     (*out).nfr = (*s).nb_frames as i32;
     (*out).tb = av_q2d((*s).time_base) as f32;
 
@@ -159,33 +399,104 @@ pub unsafe fn wrapper_get_meta(s: *mut AVStream, kind: i32, out: *mut WrapperStr
         (*out).dur = ((*s).duration as f64 * av_q2d((*s).time_base)) as f32;
     }
 
+    let mut size = 0;
+    let matrix = avformat::av_stream_get_side_data(
+        s,
+        AVPacketSideDataType_AV_PKT_DATA_DISPLAYMATRIX,
+        &mut size,
+    ) as *const i32;
+    if !matrix.is_null() && size as usize >= 9 * std::mem::size_of::<i32>() {
+        let m = std::slice::from_raw_parts(matrix, 9);
+        let (rtt, mirror) = display_matrix_orientation(m);
+        (*out).rtt = rtt;
+        (*out).mirror = mirror;
+        return;
+    }
+
     let mut tag = ptr::null_mut::<AVDictionaryEntry>();
     let rotate = CString::new("rotate").unwrap();
     tag = avformat::av_dict_get((*s).metadata, rotate.as_ptr(), tag, 0);
-    if tag.is_null() {
-        (*out).rtt = 0;
-    } else {
+    if !tag.is_null() {
         (*out).rtt = avformat::atoi((*tag).value) % 360;
     }
 }
 
+fn display_matrix_orientation(m: &[i32]) -> (c_int, c_int) {
+    let (mut a, b, mut c, d) = (m[0] as f64, m[1] as f64, m[3] as f64, m[4] as f64);
+    let mirrored = a * d - b * c < 0.0;
+    if mirrored {
+        a = -a;
+        c = -c;
+    }
+    // clockwise like the rotate tag, i.e. the negated av_display_rotation_get
+    let theta = (b / b.hypot(d)).atan2(a / a.hypot(c)).to_degrees();
+    let deg = (theta.round() as c_int).rem_euclid(360);
+    (deg, mirrored as c_int)
+}
+
 pub unsafe fn extract_next_frame(
     format_context: *mut AVFormatContext,
     ctx: *mut WrapperDecodeCtx,
     stream_index: i32,
+) -> i32 {
+    let ret = decode_next_frame(format_context, ctx, stream_index);
+    if ret < 0 {
+        return ret;
+    }
+    frame_to_rawdata((*ctx).cctx, (*ctx).frame, &mut (*ctx).output)
+}
+
+pub unsafe fn extract_next_frame_into(
+    format_context: *mut AVFormatContext,
+    ctx: *mut WrapperDecodeCtx,
+    stream_index: i32,
+    dst: *mut u8,
+    dst_size: c_int,
+) -> i32 {
+    let ret = decode_next_frame(format_context, ctx, stream_index);
+    if ret < 0 {
+        return ret;
+    }
+    let ret = frame_to_buffer((*ctx).cctx, (*ctx).frame, &mut (*ctx).output, dst, dst_size);
+    if ret == -(ENOBUFS as i32) {
+        // keep the frame so the caller can retry with a larger buffer
+        (*ctx).held = 1;
+    }
+    ret
+}
+
+pub unsafe fn decode_next_frame(
+    format_context: *mut AVFormatContext,
+    ctx: *mut WrapperDecodeCtx,
+    stream_index: i32,
 ) -> i32 {
     if (*(*ctx).cctx).codec.is_null() {
         return -99999;
     }
 
+    if (*ctx).held != 0 {
+        (*ctx).held = 0;
+        return 0;
+    }
+
     let mut ret;
 
     loop {
         if (*ctx).need_sent != 0 {
             ret = next_packet_for_stream(format_context, stream_index, (*ctx).packet);
+            if ret == AVERROR_EOF && (*ctx).draining == 0 {
+                // flush the frames still buffered in the decoder
+                (*ctx).draining = 1;
+                avformat::avcodec_send_packet((*ctx).cctx, ptr::null());
+                (*ctx).need_sent = 0;
+                continue;
+            }
             if ret < 0 {
                 return ret;
             }
+            if (*ctx).key_only != 0 && (*(*ctx).packet).flags & AV_PKT_FLAG_KEY as c_int == 0 {
+                continue;
+            }
 
             ret = avformat::avcodec_send_packet((*ctx).cctx, (*ctx).packet);
 
@@ -205,16 +516,35 @@ pub unsafe fn extract_next_frame(
         );
         let stream: *mut AVStream = streams[stream_index as usize];
 
-        if ret == AVERROR_EOF || ret == -(EAGAIN as i32) {
+        if ret == AVERROR_EOF && (*ctx).draining != 0 {
+            return ret;
+        } else if ret == AVERROR_EOF || ret == -(EAGAIN as i32) {
             (*ctx).need_sent = 1;
             continue;
         } else if ret < 0 {
             return ret;
         } else if ret >= 0 {
-            (*ctx).pts =
-                (*(*ctx).frame).best_effort_timestamp as f32 * (av_q2d((*stream).time_base) as f32);
-            (*ctx).dts = (*(*ctx).frame).pkt_dts as f32 * (av_q2d((*stream).time_base) as f32);
-            return frame_to_rawdata_rgb((*ctx).cctx, (*ctx).frame, &mut (*ctx).output);
+            if (*ctx).seek_target != AV_NOPTS_VALUE {
+                let pts = (*(*ctx).frame).best_effort_timestamp;
+                if pts != AV_NOPTS_VALUE && pts < (*ctx).seek_target {
+                    continue;
+                }
+                (*ctx).seek_target = AV_NOPTS_VALUE;
+            }
+            // frames flushed out of the decoder may come without a packet dts
+            let pts = (*(*ctx).frame).best_effort_timestamp;
+            let dts = match (*(*ctx).frame).pkt_dts {
+                AV_NOPTS_VALUE => pts,
+                dts => dts,
+            };
+            if pts != AV_NOPTS_VALUE {
+                (*ctx).last_pts = pts;
+                (*ctx).pts = pts as f32 * (av_q2d((*stream).time_base) as f32);
+            }
+            if dts != AV_NOPTS_VALUE {
+                (*ctx).dts = dts as f32 * (av_q2d((*stream).time_base) as f32);
+            }
+            return 0;
         }
     }
 }
@@ -224,14 +554,29 @@ pub unsafe fn wrapper_stream_seek_by_time(
     c: *mut AVFormatContext,
     cc: *mut AVCodecContext,
     position: f32,
+    target: *mut i64,
 ) -> i32 {
     let mut ts: i64 = (position as f64 / av_q2d((*s).time_base)) as i64;
 
-    if (*c).start_time != AV_NOPTS_VALUE {
-        ts += (*c).start_time;
+    if (*s).start_time != AV_NOPTS_VALUE {
+        ts += (*s).start_time;
     }
 
-    let rc = avformat::av_seek_frame(c, (*s).index, ts, AVSEEK_FLAG_ANY as i32);
+    let rc = wrapper_stream_seek_by_ts(s, c, cc, ts);
+    if rc < 0 {
+        return rc;
+    }
+    *target = ts;
+    rc
+}
+
+pub unsafe fn wrapper_stream_seek_by_ts(
+    s: *mut AVStream,
+    c: *mut AVFormatContext,
+    cc: *mut AVCodecContext,
+    ts: i64,
+) -> i32 {
+    let rc = avformat::av_seek_frame(c, (*s).index, ts, AVSEEK_FLAG_BACKWARD as i32);
     if rc < 0 {
         return rc;
     }
@@ -263,6 +608,15 @@ pub unsafe fn wrapper_stream_create_decode_ctx(
         return -3;
     }
 
+    if (*out).thread_count >= 0 {
+        (*(*out).cctx).thread_count = (*out).thread_count;
+    }
+    if (*out).thread_type >= 0 {
+        (*(*out).cctx).thread_type = (*out).thread_type;
+    }
+    (*(*out).cctx).lowres = (*out).lowres;
+    wrapper_apply_skip_options(out);
+
     ret = avformat::avcodec_open2((*out).cctx, codec, ptr::null_mut());
     if ret < 0 {
         return -4;
@@ -277,6 +631,15 @@ pub unsafe fn wrapper_stream_create_decode_ctx(
     return 0;
 }
 
+pub unsafe fn wrapper_apply_skip_options(ctx: *mut WrapperDecodeCtx) {
+    if (*ctx).cctx.is_null() {
+        return;
+    }
+    (*(*ctx).cctx).skip_frame = (*ctx).skip_frame;
+    (*(*ctx).cctx).skip_loop_filter = (*ctx).skip_loop_filter;
+    (*(*ctx).cctx).skip_idct = (*ctx).skip_idct;
+}
+
 pub unsafe fn read_and_convert_audio_frame(
     swr_context: *mut SwrContext,
     fifo: *mut AVAudioFifo,
@@ -380,11 +743,25 @@ pub unsafe fn wrapper_destroy_stream(ctx: *mut WrapperDecodeCtx) {
     if !(*ctx).cctx.is_null() {
         avformat::avcodec_free_context(&mut (*ctx).cctx);
     }
-    if !(*ctx).output.allocated != 0 {
-        avformat::av_freep((*ctx).output.bgr24_buf.as_mut_ptr() as (*mut c_void));
-        swscale::sws_freeContext((*ctx).output.img_convert_ctx);
-        (*ctx).output.allocated = 0;
+    wrapper_reset_output(&mut (*ctx).output);
+}
+
+pub unsafe fn wrapper_reset_output(output: *mut WrapperFrameOutput) {
+    if (*output).allocated != 0 {
+        avformat::av_freep((*output).image_buf.as_mut_ptr() as *mut c_void);
+        (*output).allocated = 0;
     }
+    if !(*output).rotate_buf[0].is_null() {
+        avformat::av_freep((*output).rotate_buf.as_mut_ptr() as *mut c_void);
+        (*output).rotate_buf = [ptr::null_mut(); 4usize];
+    }
+    if !(*output).img_convert_ctx.is_null() {
+        swscale::sws_freeContext((*output).img_convert_ctx);
+        (*output).img_convert_ctx = ptr::null_mut();
+    }
+    (*output).dst_buf = [ptr::null_mut(); 4usize];
+    (*output).buffer = ptr::null_mut();
+    (*output).buffersize = 0;
 }
 
 pub unsafe fn wrapper_stream_decode_audio(
@@ -422,11 +799,14 @@ pub unsafe fn wrapper_stream_decode_audio(
     let mut decoding_frame: *mut AVFrame = ptr::null_mut();
     let mut output_samples: *mut *mut u8 = ptr::null_mut();
 
-    let mut packet: AVPacket = std::mem::uninitialized();
+    let mut packet: AVPacket = std::mem::zeroed();
     avformat::av_init_packet(&mut packet);
 
     loop {
         let mut src_channel = (*codec_context).channel_layout;
+        if src_channel == 0 {
+            src_channel = avformat::av_get_default_channel_layout((*codec_context).channels) as u64;
+        }
         if src_channel == 0 {
             src_channel = 1;
         }
@@ -558,3 +938,34 @@ pub unsafe fn wrapper_stream_decode_audio(
 
     return rc;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::display_matrix_orientation;
+
+    const ONE: i32 = 1 << 16;
+
+    fn matrix(a: i32, b: i32, c: i32, d: i32) -> [i32; 9] {
+        [a, b, 0, c, d, 0, 0, 0, 1 << 30]
+    }
+
+    #[test]
+    fn test_display_matrix_orientation() {
+        // matrices as written by av_display_rotation_set(-rotate) and av_display_matrix_flip
+        assert_eq!((0, 0), display_matrix_orientation(&matrix(ONE, 0, 0, ONE)));
+        assert_eq!(
+            (90, 0),
+            display_matrix_orientation(&matrix(0, ONE, -ONE, 0))
+        );
+        assert_eq!(
+            (180, 0),
+            display_matrix_orientation(&matrix(-ONE, 0, 0, -ONE))
+        );
+        assert_eq!(
+            (270, 0),
+            display_matrix_orientation(&matrix(0, -ONE, ONE, 0))
+        );
+        assert_eq!((0, 1), display_matrix_orientation(&matrix(-ONE, 0, 0, ONE)));
+        assert_eq!((90, 1), display_matrix_orientation(&matrix(0, ONE, ONE, 0)));
+    }
+}
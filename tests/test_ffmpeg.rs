@@ -1,10 +1,19 @@
 use std::error::Error;
 use std::fs::File;
-use std::io::Read;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 extern crate ffmpeg;
+use ffmpeg::error::ErrorKind;
 use ffmpeg::format;
-use ffmpeg::{Orientation, Stream};
+use ffmpeg::frame::PictureType;
+use ffmpeg::{
+    remux, BitstreamFilter, CancelToken, Discard, Format, Orientation, OutputSpec, PixelFormat,
+    Resize, SampleMode, ScaleAlgorithm, SeekMode, Stream, ThreadType, Threading,
+};
 
 fn traverse_frame(stream: &mut Stream, width: i32, height: i32, stride: i32) {
     let mut frame_count = 0;
@@ -77,6 +86,745 @@ fn test_video_from_blob() {
     }
 }
 
+#[test]
+fn test_video_from_slice() {
+    ffmpeg::init();
+    for format in ["flv", "mp4", "mov", "avi"].iter() {
+        let file_name = &format!("fixture/video/example.{}", format)[..];
+        let mut blob = Vec::new();
+        File::open(file_name)
+            .unwrap()
+            .read_to_end(&mut blob)
+            .unwrap();
+
+        let mut fm = format::load_video_from_slice(&blob).unwrap();
+        assert_eq!(1, fm.video_streams().len());
+        traverse_frame(&mut fm.video_streams()[0], 640, 360, 1920);
+    }
+}
+
+#[test]
+fn test_video_from_shared() {
+    ffmpeg::init();
+    let mut blob = Vec::new();
+    File::open("fixture/video/example.mp4")
+        .unwrap()
+        .read_to_end(&mut blob)
+        .unwrap();
+    let blob: Arc<[u8]> = blob.into();
+
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let blob = blob.clone();
+            thread::spawn(move || {
+                let mut fm = format::load_video_from_shared(blob).unwrap();
+                assert_eq!(1, fm.video_streams().len());
+                traverse_frame(&mut fm.video_streams()[0], 640, 360, 1920);
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+}
+
+#[test]
+fn test_video_from_reader() {
+    ffmpeg::init();
+    for format in ["flv", "mp4", "mov", "avi"].iter() {
+        let file = File::open(&format!("fixture/video/example.{}", format)[..]).unwrap();
+        let mut fm = format::load_video_from_reader(file).unwrap();
+        assert_eq!(1, fm.video_streams().len());
+        traverse_frame(&mut fm.video_streams()[0], 640, 360, 1920);
+    }
+}
+
+struct PanickingReader;
+
+impl Read for PanickingReader {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+        panic!("boom")
+    }
+}
+
+impl Seek for PanickingReader {
+    fn seek(&mut self, _: SeekFrom) -> io::Result<u64> {
+        Err(io::Error::new(io::ErrorKind::Other, "unseekable"))
+    }
+}
+
+#[test]
+fn test_video_from_panicking_reader() {
+    ffmpeg::init();
+    let err = format::load_video_from_reader(PanickingReader).unwrap_err();
+    assert_eq!("ffmpeg_open: reader panicked: boom", err.description())
+}
+
+struct FailingReader(File, Arc<AtomicBool>);
+
+impl Read for FailingReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.1.load(Ordering::SeqCst) {
+            return Err(io::Error::new(io::ErrorKind::Other, "disk gone"));
+        }
+        self.0.read(buf)
+    }
+}
+
+impl Seek for FailingReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+#[test]
+fn test_audio_from_failing_reader() {
+    ffmpeg::init();
+    let fail = Arc::new(AtomicBool::new(false));
+    let file = File::open("fixture/video/audio.mp3").unwrap();
+    let mut fm = format::load_video_from_reader(FailingReader(file, fail.clone())).unwrap();
+    fail.store(true, Ordering::SeqCst);
+    let err = fm.audio_streams()[0].get_audio_data(1, 16000).unwrap_err();
+    assert_eq!(ErrorKind::Io, err.kind());
+    assert_eq!("ffmpeg_stream_decode_audio: disk gone", err.description());
+}
+
+struct Pipe(File);
+
+impl Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+#[test]
+fn test_video_from_stream() {
+    ffmpeg::init();
+    for format in ["flv", "avi"].iter() {
+        let file = File::open(&format!("fixture/video/example.{}", format)[..]).unwrap();
+        let mut fm = format::load_video_from_stream(Pipe(file)).unwrap();
+        assert!(!fm.is_seekable());
+        assert_eq!(1, fm.video_streams().len());
+
+        let vs = &mut fm.video_streams()[0];
+        let err = vs.seek_by_time(1.5).unwrap_err();
+        assert_eq!(ErrorKind::NotSeekable, err.kind());
+        traverse_frame(vs, 640, 360, 1920);
+    }
+}
+
+#[test]
+fn test_open_options() {
+    ffmpeg::init();
+    let mut fm = Format::builder()
+        .probesize(5_000_000)
+        .analyzeduration(Duration::from_secs(2))
+        .ignore_editlist(true)
+        .option("not_an_option", "1")
+        .stream_info_option("threads", "1")
+        .open_file("fixture/video/example.mp4")
+        .unwrap();
+    assert_eq!(vec!["not_an_option".to_string()], fm.unused_options);
+    assert_eq!(1, fm.video_streams().len());
+
+    let mut blob = Vec::new();
+    File::open("fixture/video/example.flv")
+        .unwrap()
+        .read_to_end(&mut blob)
+        .unwrap();
+    let mut fm = Format::builder().fflags("+genpts").open_blob(blob).unwrap();
+    assert!(fm.unused_options.is_empty());
+    traverse_frame(&mut fm.video_streams()[0], 640, 360, 1920);
+}
+
+#[test]
+fn test_raw_video_input() {
+    ffmpeg::init();
+    let blob = vec![128u8; 64 * 48 * 3];
+    let mut fm = Format::builder()
+        .input_format("rawvideo")
+        .video_size(64, 48)
+        .pixel_format("gray")
+        .framerate(25.0)
+        .open_slice(&blob)
+        .unwrap();
+    assert_eq!(1, fm.video_streams().len());
+    traverse_frame(&mut fm.video_streams()[0], 64, 48, 192);
+}
+
+#[test]
+fn test_raw_pcm_input() {
+    ffmpeg::init();
+    let blob = vec![0u8; 16000 * 2];
+    let mut fm = Format::builder()
+        .input_format("s16le")
+        .sample_rate(16000)
+        .channels(1)
+        .open_blob(blob)
+        .unwrap();
+    assert_eq!(1, fm.audio_streams().len());
+
+    let pcm = fm.audio_streams()[0].get_audio_data(1, 16000).unwrap();
+    assert_eq!(16000 * 2, pcm.len());
+}
+
+#[test]
+fn test_unknown_input_format() {
+    ffmpeg::init();
+    let err = Format::builder()
+        .input_format("not_a_demuxer")
+        .open_file("fixture/video/example.mp4")
+        .unwrap_err();
+    assert_eq!("ffmpeg_open: Demuxer not found", err.description())
+}
+
+#[test]
+fn test_probe() {
+    ffmpeg::init();
+    for (file_name, name) in [
+        ("fixture/video/example.mp4", "mov,mp4,m4a,3gp,3g2,mj2"),
+        ("fixture/video/example.flv", "flv"),
+        ("fixture/video/audio.mp3", "mp3"),
+    ]
+    .iter()
+    {
+        let mut head = vec![0u8; 4096];
+        let n = File::open(file_name).unwrap().read(&mut head).unwrap();
+        let result = format::probe(&head[..n]).unwrap();
+        assert_eq!(*name, result.name);
+        assert!(result.score > 0);
+    }
+}
+
+#[test]
+fn test_cancel() {
+    ffmpeg::init();
+    let token = CancelToken::new();
+    token.cancel();
+    let err = Format::builder()
+        .cancel_token(&token)
+        .open_file("fixture/video/example.mp4")
+        .unwrap_err();
+    assert_eq!(ErrorKind::Cancelled, err.kind());
+
+    let token = CancelToken::new();
+    let mut fm = Format::builder()
+        .cancel_token(&token)
+        .open_file("fixture/video/example.mp4")
+        .unwrap();
+    let vs = &mut fm.video_streams()[0];
+    vs.next_video_frame().unwrap();
+    token.cancel();
+    let err = loop {
+        if let Err(err) = vs.next_video_frame() {
+            break err;
+        }
+    };
+    assert_eq!(ErrorKind::Cancelled, err.kind());
+
+    let blob = std::fs::read("fixture/video/example.mp4").unwrap();
+    let token = CancelToken::new();
+    let mut fm = Format::builder()
+        .cancel_token(&token)
+        .open_blob(blob)
+        .unwrap();
+    let vs = &mut fm.video_streams()[0];
+    vs.next_video_frame().unwrap();
+    token.cancel();
+    let err = loop {
+        if let Err(err) = vs.next_video_frame() {
+            break err;
+        }
+    };
+    assert_eq!(ErrorKind::Cancelled, err.kind());
+}
+
+#[test]
+fn test_timeout() {
+    ffmpeg::init();
+    let err = Format::builder()
+        .timeout(Duration::from_secs(0))
+        .open_file("fixture/video/example.mp4")
+        .unwrap_err();
+    assert_eq!(ErrorKind::TimedOut, err.kind());
+    assert_eq!("ffmpeg_open: operation timed out", err.description());
+
+    let blob = std::fs::read("fixture/video/example.mp4").unwrap();
+    let mut fm = Format::builder()
+        .timeout(Duration::from_millis(500))
+        .open_blob(blob)
+        .unwrap();
+    thread::sleep(Duration::from_millis(600));
+    let vs = &mut fm.video_streams()[0];
+    let err = loop {
+        if let Err(err) = vs.next_video_frame() {
+            break err;
+        }
+    };
+    assert_eq!(ErrorKind::TimedOut, err.kind());
+}
+
+#[test]
+fn test_read_packets() {
+    ffmpeg::init();
+    let mut fm = format::load_video_from_file("fixture/video/example.mp4").unwrap();
+    let video_index = fm.video_streams()[0].index();
+
+    let mut n_video = 0;
+    let mut n_key = 0;
+    let mut last_dts = None;
+    for packet in fm.packets() {
+        let packet = packet.unwrap();
+        assert!(packet.stream_index() >= 0 && packet.stream_index() < 2);
+        assert!(!packet.data().is_empty());
+        if packet.stream_index() != video_index {
+            continue;
+        }
+        if n_video == 0 {
+            assert!(packet.is_key());
+        }
+        if packet.is_key() {
+            n_key += 1;
+        }
+        assert!(packet.dts() >= last_dts);
+        last_dts = packet.dts();
+        n_video += 1;
+    }
+    assert!(n_video > 0);
+    assert!(n_key > 0 && n_key <= n_video);
+
+    let err = fm.read_packet().unwrap_err();
+    assert_eq!(ErrorKind::Eof, err.kind());
+}
+
+#[test]
+fn test_bitstream_filter() {
+    ffmpeg::init();
+    let mut fm = format::load_video_from_file("fixture/video/example.mp4").unwrap();
+    let mut bsf = BitstreamFilter::new("h264_mp4toannexb", &fm.video_streams()[0]).unwrap();
+
+    let mut filtered = Vec::new();
+    for packet in fm.packets() {
+        let packet = packet.unwrap();
+        if packet.stream_index() == bsf.stream_index() {
+            filtered.extend(bsf.filter(packet).unwrap());
+        }
+    }
+    filtered.extend(bsf.flush().unwrap());
+
+    assert!(!filtered.is_empty());
+    for packet in filtered.iter() {
+        let data = packet.data();
+        assert!(data.starts_with(&[0, 0, 0, 1]) || data.starts_with(&[0, 0, 1]));
+    }
+
+    let err = BitstreamFilter::new("not_a_filter", &fm.video_streams()[0]).unwrap_err();
+    assert_eq!("bsf_parse: Bitstream filter not found", err.description());
+}
+
+#[test]
+fn test_remux() {
+    ffmpeg::init();
+    for format in ["flv", "mov", "avi"].iter() {
+        let mut input =
+            format::load_video_from_file(&format!("fixture/video/example.{}", format)[..])
+                .unwrap();
+        let mut sink = Cursor::new(Vec::new());
+        remux(&mut input, OutputSpec::writer(&mut sink, "matroska")).unwrap();
+
+        let mut fm = format::load_video_from_blob(sink.into_inner()).unwrap();
+        assert_eq!(input.n_streams, fm.n_streams);
+        traverse_frame(&mut fm.video_streams()[0], 640, 360, 1920);
+    }
+
+    let path = std::env::temp_dir().join("ffmpeg_test_remux.mp4");
+    let path = path.to_str().unwrap();
+    let mut input = format::load_video_from_file("fixture/video/example.flv").unwrap();
+    let video_index = input.video_streams()[0].index();
+    let output = OutputSpec::file(path)
+        .streams(&[video_index])
+        .option("movflags", "+faststart");
+    remux(&mut input, output).unwrap();
+
+    let mut fm = format::load_video_from_file(path).unwrap();
+    assert_eq!(1, fm.n_streams);
+    traverse_frame(&mut fm.video_streams()[0], 640, 360, 1920);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_remux_after_decoding() {
+    ffmpeg::init();
+    fn count_frames(fm: &mut Format) -> usize {
+        let vs = &mut fm.video_streams()[0];
+        let mut count = 0;
+        while vs.next_video_frame().is_ok() {
+            count += 1;
+        }
+        count
+    }
+    let mut fm = format::load_video_from_file("fixture/video/example.flv").unwrap();
+    let total = count_frames(&mut fm);
+
+    let mut input = format::load_video_from_file("fixture/video/example.flv").unwrap();
+    assert!(input.is_seekable());
+    {
+        let vs = &mut input.video_streams()[0];
+        for _ in 0..10 {
+            vs.next_video_frame().unwrap();
+        }
+    }
+    let mut sink = Cursor::new(Vec::new());
+    remux(&mut input, OutputSpec::writer(&mut sink, "matroska")).unwrap();
+
+    let mut fm = format::load_video_from_blob(sink.into_inner()).unwrap();
+    let first = fm.video_streams()[0].next_video_frame().unwrap();
+    assert!(first.pts < 0.0001);
+    assert_eq!(total - 1, count_frames(&mut fm));
+}
+
+#[test]
+fn test_pixel_formats() {
+    ffmpeg::init();
+    let mut fm = format::load_video_from_file("fixture/video/example.mp4").unwrap();
+    let vs = &mut fm.video_streams()[0];
+    assert_eq!(PixelFormat::Bgr24, vs.pixel_format());
+
+    let frame = vs.next_video_frame().unwrap();
+    assert_eq!(PixelFormat::Bgr24, frame.format);
+    assert_eq!(1, frame.planes.len());
+    assert_eq!(1920 * 360, frame.plane(0).len());
+
+    vs.set_pixel_format(PixelFormat::Rgba);
+    let frame = vs.next_video_frame().unwrap();
+    assert_eq!(2560, frame.stride);
+    assert_eq!(2560 * 360, frame.plane(0).len());
+
+    vs.set_pixel_format(PixelFormat::Gray8);
+    let frame = vs.next_video_frame().unwrap();
+    assert_eq!(1, frame.planes.len());
+    assert_eq!(640, frame.stride);
+
+    vs.set_pixel_format(PixelFormat::Yuv420p);
+    let frame = vs.next_video_frame().unwrap();
+    let layout: Vec<_> = frame.planes.iter().map(|p| (p.stride, p.height)).collect();
+    assert_eq!(vec![(640, 360), (320, 180), (320, 180)], layout);
+    assert_eq!(320 * 180, frame.plane(2).len());
+
+    vs.set_pixel_format(PixelFormat::Nv12);
+    let frame = vs.next_video_frame().unwrap();
+    let layout: Vec<_> = frame.planes.iter().map(|p| (p.stride, p.height)).collect();
+    assert_eq!(vec![(640, 360), (640, 180)], layout);
+
+    vs.set_pixel_format(PixelFormat::Rgb48);
+    let frame = vs.next_video_frame().unwrap();
+    assert_eq!(3840, frame.stride);
+}
+
+#[test]
+fn test_resize() {
+    ffmpeg::init();
+    let mut fm = format::load_video_from_file("fixture/video/example.mp4").unwrap();
+    let vs = &mut fm.video_streams()[0];
+
+    vs.set_resize(Some(Resize::Exact(100, 50))).unwrap();
+    let frame = vs.next_video_frame().unwrap();
+    assert_eq!((100, 50, 300), (frame.width, frame.height, frame.stride));
+
+    vs.set_scale_algorithm(ScaleAlgorithm::Lanczos);
+    vs.set_resize(Some(Resize::Fit(320, 320))).unwrap();
+    let frame = vs.next_video_frame().unwrap();
+    assert_eq!((320, 180), (frame.width, frame.height));
+
+    vs.set_scale_algorithm(ScaleAlgorithm::Area);
+    vs.set_resize(Some(Resize::Scale(0.25))).unwrap();
+    let frame = vs.next_video_frame().unwrap();
+    assert_eq!((160, 90), (frame.width, frame.height));
+
+    vs.set_resize(None).unwrap();
+    let frame = vs.next_video_frame().unwrap();
+    assert_eq!((640, 360), (frame.width, frame.height));
+
+    let err = vs.set_resize(Some(Resize::Exact(0, 10))).unwrap_err();
+    assert_eq!("invalid resize: Invalid argument", err.description());
+}
+
+#[test]
+fn test_auto_rotate() {
+    ffmpeg::init();
+    let mut fm = format::load_video_from_file("fixture/video/example.mp4").unwrap();
+    let vs = &mut fm.video_streams()[0];
+    let upright = vs.next_video_frame().unwrap();
+
+    vs.seek_by_time(0.0).unwrap();
+    vs.orientation = Orientation::Left;
+    vs.set_auto_rotate(true);
+    assert!(vs.auto_rotate());
+    let frame = vs.next_video_frame().unwrap();
+    assert_eq!((360, 640, 1080), (frame.width, frame.height, frame.stride));
+    let top_left = &upright.buffer[359 * 1920..359 * 1920 + 3];
+    assert_eq!(top_left, &frame.buffer[0..3]);
+
+    vs.set_pixel_format(PixelFormat::Yuv420p);
+    let frame = vs.next_video_frame().unwrap();
+    let layout: Vec<_> = frame.planes.iter().map(|p| (p.stride, p.height)).collect();
+    assert_eq!(vec![(360, 640), (180, 320), (180, 320)], layout);
+
+    vs.orientation = Orientation::Bottom;
+    vs.set_auto_rotate(true);
+    let frame = vs.next_video_frame().unwrap();
+    assert_eq!((640, 360), (frame.width, frame.height));
+
+    vs.set_auto_rotate(false);
+    let frame = vs.next_video_frame().unwrap();
+    assert_eq!((640, 360), (frame.width, frame.height));
+}
+
+#[test]
+fn test_mirrored_orientation() {
+    ffmpeg::init();
+    assert_eq!(270, Orientation::RightMirrored.degrees());
+    assert!(Orientation::RightMirrored.is_mirrored());
+    assert!(!Orientation::Left.is_mirrored());
+
+    let mut fm = format::load_video_from_file("fixture/video/example.mp4").unwrap();
+    let vs = &mut fm.video_streams()[0];
+    assert_eq!((0, false), (vs.rotation(), vs.is_mirrored()));
+    let upright = vs.next_video_frame().unwrap();
+
+    vs.seek_by_time(0.0).unwrap();
+    vs.orientation = Orientation::TopMirrored;
+    vs.set_auto_rotate(true);
+    let frame = vs.next_video_frame().unwrap();
+    assert_eq!((640, 360), (frame.width, frame.height));
+    assert_eq!(&upright.buffer[639 * 3..640 * 3], &frame.buffer[0..3]);
+
+    vs.orientation = Orientation::LeftMirrored;
+    vs.set_auto_rotate(true);
+    let frame = vs.next_video_frame().unwrap();
+    assert_eq!((360, 640), (frame.width, frame.height));
+}
+
+#[test]
+fn test_frame_ref() {
+    ffmpeg::init();
+    let mut fm = format::load_video_from_file("fixture/video/example.mp4").unwrap();
+    let vs = &mut fm.video_streams()[0];
+
+    let mut frame_count = 0;
+    loop {
+        let frame = match vs.next_frame_ref() {
+            Ok(frame) => frame,
+            Err(err) => {
+                assert_eq!(ErrorKind::Eof, err.kind());
+                break;
+            }
+        };
+        assert_eq!(Some(PixelFormat::Yuv420p), frame.pixel_format());
+        assert_eq!("yuv420p", frame.format_name());
+        assert_eq!((640, 360), (frame.width(), frame.height()));
+        assert_eq!(3, frame.n_planes());
+        assert!(frame.linesize(0) >= 640);
+        assert_eq!((frame.linesize(0) * 360) as usize, frame.plane(0).len());
+        assert_eq!((frame.linesize(1) * 180) as usize, frame.plane(1).len());
+        assert!(frame.pts() > -0.0001);
+        frame_count += 1;
+    }
+    assert!(frame_count > 0);
+}
+
+#[test]
+fn test_video_frame_into() {
+    ffmpeg::init();
+    let mut fm = format::load_video_from_file("fixture/video/example.mp4").unwrap();
+    let first = fm.video_streams()[0].next_video_frame().unwrap();
+    fm.video_streams()[0].next_video_frame().unwrap();
+    let third = fm.video_streams()[0].next_video_frame().unwrap();
+
+    let mut fm = format::load_video_from_file("fixture/video/example.mp4").unwrap();
+    let vs = &mut fm.video_streams()[0];
+    assert_eq!(1920 * 360, vs.frame_size());
+    let mut buf = vec![0u8; vs.frame_size()];
+    let info = vs.next_video_frame_into(&mut buf).unwrap();
+    assert_eq!((640, 360, 1920), (info.width, info.height, info.stride));
+    assert_eq!(buf.len(), info.size);
+    assert_eq!(first.buffer, buf);
+
+    vs.set_pixel_format(PixelFormat::Yuv420p);
+    vs.set_resize(Some(Resize::Exact(320, 180))).unwrap();
+    assert_eq!(320 * 180 * 3 / 2, vs.frame_size());
+    let info = vs.next_video_frame_into(&mut buf).unwrap();
+    assert_eq!(320 * 180 * 3 / 2, info.size);
+    let layout: Vec<_> = info.planes.iter().map(|p| (p.offset, p.stride)).collect();
+    assert_eq!(
+        vec![(0, 320), (320 * 180, 160), (320 * 180 * 5 / 4, 160)],
+        layout
+    );
+
+    let mut small = vec![0u8; 16];
+    let err = vs.next_video_frame_into(&mut small).unwrap_err();
+    assert_eq!("next_frame: No buffer space available", err.description());
+    let info = vs.next_video_frame_into(&mut buf).unwrap();
+    assert_eq!(third.meta.pts, info.meta.pts);
+}
+
+#[test]
+fn test_threading() {
+    ffmpeg::init();
+    let mut fm = format::load_video_from_file("fixture/video/example.mp4").unwrap();
+    let vs = &mut fm.video_streams()[0];
+    assert_eq!(None, vs.threading());
+    vs.set_threading(Threading::all_cores()).unwrap();
+    assert_eq!(Some(Threading::all_cores()), vs.threading());
+    traverse_frame(vs, 640, 360, 1920);
+    let err = vs.set_threading(Threading::single_threaded()).unwrap_err();
+    assert_eq!(
+        "decoder already opened: Device or resource busy",
+        err.description()
+    );
+
+    let mut fm = format::load_video_from_file("fixture/video/example.mp4").unwrap();
+    let vs = &mut fm.video_streams()[0];
+    vs.set_threading(Threading::new(4, ThreadType::Slice))
+        .unwrap();
+    traverse_frame(vs, 640, 360, 1920);
+}
+
+#[test]
+fn test_fast_decode_modes() {
+    ffmpeg::init();
+    let mut fm = format::load_video_from_file("fixture/video/example.mp4").unwrap();
+    let vs = &mut fm.video_streams()[0];
+    let mut n_keyframes = 0;
+    for frame in vs.keyframes() {
+        let frame = frame.unwrap();
+        assert_eq!((640, 360), (frame.width, frame.height));
+        n_keyframes += 1;
+    }
+    assert!(n_keyframes > 0 && n_keyframes < vs.n_frame);
+    assert_eq!(Discard::Default, vs.skip_frame());
+
+    let mut fm = format::load_video_from_file("fixture/video/example.mp4").unwrap();
+    let vs = &mut fm.video_streams()[0];
+    assert_eq!(0, vs.max_lowres());
+    let err = vs.set_lowres(1).unwrap_err();
+    assert_eq!("lowres not supported: Invalid argument", err.description());
+    assert_eq!(0, vs.lowres());
+    vs.set_skip_loop_filter(Discard::All);
+    vs.set_skip_idct(Discard::NonRef);
+    vs.set_skip_frame(Discard::Bidir);
+    assert_eq!(Discard::Bidir, vs.skip_frame());
+    traverse_frame(vs, 640, 360, 1920);
+    let err = vs.set_lowres(0).unwrap_err();
+    assert_eq!(
+        "decoder already opened: Device or resource busy",
+        err.description()
+    );
+}
+
+#[test]
+fn test_frame_meta() {
+    ffmpeg::init();
+    let mut fm = format::load_video_from_file("fixture/video/example.mp4").unwrap();
+    let vs = &mut fm.video_streams()[0];
+
+    let frame = vs.next_video_frame().unwrap();
+    assert!(frame.meta.key_frame);
+    assert_eq!(PictureType::I, frame.meta.pict_type);
+    let (num, den) = frame.meta.time_base;
+    let pts = frame.meta.pts.unwrap();
+    assert!((pts as f32 * num as f32 / den as f32 - frame.pts).abs() < 0.001);
+    assert!(frame.meta.pkt_duration > 0);
+    assert!(!frame.meta.interlaced);
+
+    let mut types = vec![];
+    while let Ok(frame) = vs.next_video_frame() {
+        assert!(!frame.meta.key_frame || frame.meta.pict_type == PictureType::I);
+        types.push(frame.meta.pict_type);
+    }
+    assert!(types.contains(&PictureType::P));
+
+    vs.seek_by_time(0.0).unwrap();
+    let frame = vs.next_frame_ref().unwrap();
+    assert_eq!(PictureType::I, frame.meta().pict_type);
+}
+
+#[test]
+fn test_source_changed() {
+    ffmpeg::init();
+    let mut fm = format::load_video_from_file("fixture/video/example.mp4").unwrap();
+    let vs = &mut fm.video_streams()[0];
+
+    let frame = vs.next_video_frame().unwrap();
+    assert!(!frame.source_changed);
+    vs.set_pixel_format(PixelFormat::Gray8);
+    vs.set_resize(Some(Resize::Scale(0.5))).unwrap();
+    assert_eq!(320 * 180, vs.frame_size());
+    let mut buf = vec![0u8; vs.frame_size()];
+    while let Ok(info) = vs.next_video_frame_into(&mut buf) {
+        assert!(!info.source_changed);
+        assert_eq!((320, 180), (info.width, info.height));
+    }
+}
+
+fn ppm(width: usize, height: usize, value: u8) -> Vec<u8> {
+    let mut data = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    data.extend(vec![value; width * height * 3]);
+    data
+}
+
+#[test]
+fn test_orientation_without_display_matrix() {
+    ffmpeg::init();
+    let mut fm = Format::builder()
+        .input_format("ppm_pipe")
+        .open_blob(ppm(32, 16, 100))
+        .unwrap();
+    let vs = &mut fm.video_streams()[0];
+    assert_eq!((0, false), (vs.rotation(), vs.is_mirrored()));
+    assert_eq!(Orientation::Top, vs.orientation);
+}
+
+#[test]
+fn test_source_changed_mid_stream() {
+    ffmpeg::init();
+    let mut blob = vec![];
+    for _ in 0..3 {
+        blob.extend(ppm(64, 48, 200));
+    }
+    for _ in 0..3 {
+        blob.extend(ppm(32, 16, 50));
+    }
+    let mut fm = Format::builder()
+        .input_format("ppm_pipe")
+        .open_blob(blob)
+        .unwrap();
+    let vs = &mut fm.video_streams()[0];
+    vs.set_pixel_format(PixelFormat::Gray8);
+    assert_eq!(64 * 48, vs.frame_size());
+
+    let mut buf = vec![0u8; vs.frame_size()];
+    let mut changed = vec![];
+    let mut sizes = vec![];
+    while let Ok(info) = vs.next_video_frame_into(&mut buf) {
+        changed.push(info.source_changed);
+        sizes.push((info.width, info.height));
+        assert_eq!(1, info.planes.len());
+        assert_eq!(
+            (0, info.width),
+            (info.planes[0].offset, info.planes[0].stride)
+        );
+        assert_eq!((info.width * info.height) as usize, info.size);
+        assert_eq!(info.size, vs.frame_size());
+        assert!(buf[..info.size].iter().all(|&p| p == buf[0]));
+    }
+    assert_eq!(vec![false, false, false, true, false, false], changed);
+    assert_eq!(
+        vec![(64, 48), (64, 48), (64, 48), (32, 16), (32, 16), (32, 16)],
+        sizes
+    );
+}
+
 #[test]
 fn test_video_not_existed() {
     ffmpeg::init();
@@ -105,6 +853,211 @@ fn test_seek() {
     assert_eq!((), vs.seek_by_frame(10).unwrap());
 }
 
+#[test]
+fn test_accurate_seek() {
+    ffmpeg::init();
+    let mut fm = format::load_video_from_file("fixture/video/example.mp4").unwrap();
+    let vs = &mut fm.video_streams()[0];
+    let frame_duration = 1.0 / vs.fps;
+
+    vs.seek_by_time(2.0).unwrap();
+    let frame = vs.next_video_frame().unwrap();
+    assert!(frame.pts >= 2.0 - 0.0001 && frame.pts < 2.0 + frame_duration);
+
+    vs.seek_by_frame(10).unwrap();
+    let frame = vs.next_video_frame().unwrap();
+    assert!((frame.pts - 10.0 * frame_duration).abs() < 0.001);
+
+    vs.seek_by_time_with(2.0, SeekMode::Keyframe).unwrap();
+    let frame = vs.next_video_frame().unwrap();
+    assert!(frame.meta.key_frame);
+    assert!(frame.pts <= 2.0 + 0.0001);
+}
+
+#[test]
+fn test_frame_at() {
+    ffmpeg::init();
+    let mut fm = format::load_video_from_file("fixture/video/example.mp4").unwrap();
+    let vs = &mut fm.video_streams()[0];
+    let mut all_pts = vec![];
+    while let Ok(frame) = vs.next_video_frame() {
+        all_pts.push(frame.meta.pts.unwrap());
+    }
+
+    let index = vs.build_index().unwrap().clone();
+    assert_eq!(all_pts.len(), index.len());
+    assert_eq!(
+        all_pts,
+        index.entries.iter().map(|e| e.pts).collect::<Vec<_>>()
+    );
+    assert!(index.entries[0].key);
+    assert_eq!(Some(0), index.keyframe_before(5));
+
+    for &n in [17, 3, 0, all_pts.len() - 1].iter() {
+        let frame = vs.frame_at(n).unwrap();
+        assert_eq!(Some(all_pts[n]), frame.meta.pts);
+    }
+    let err = vs.frame_at(all_pts.len()).unwrap_err();
+    assert_eq!(
+        "frame number out of range: Invalid argument",
+        err.description()
+    );
+
+    vs.seek_by_frame(25).unwrap();
+    let frame = vs.next_video_frame().unwrap();
+    assert_eq!(Some(all_pts[25]), frame.meta.pts);
+}
+
+#[test]
+fn test_frame_index_after_partial_read() {
+    ffmpeg::init();
+    for format in ["flv", "mov"].iter() {
+        let path = format!("fixture/video/example.{}", format);
+        let mut fm = format::load_video_from_file(&path).unwrap();
+        let mut all_pts = vec![];
+        while let Ok(frame) = fm.video_streams()[0].next_video_frame() {
+            all_pts.push(frame.meta.pts.unwrap());
+        }
+
+        let mut fm = format::load_video_from_file(&path).unwrap();
+        let vs = &mut fm.video_streams()[0];
+        for _ in 0..10 {
+            vs.next_video_frame().unwrap();
+        }
+        let index = vs.build_index().unwrap().clone();
+        let pts: Vec<_> = index.entries.iter().map(|e| e.pts).collect();
+        assert_eq!(all_pts, pts);
+        assert_eq!(Some(all_pts[10]), vs.next_video_frame().unwrap().meta.pts);
+        for &n in [40, 5, all_pts.len() - 1].iter() {
+            assert_eq!(Some(all_pts[n]), vs.frame_at(n).unwrap().meta.pts);
+        }
+    }
+}
+
+#[test]
+fn test_load_index() {
+    ffmpeg::init();
+    let path = "fixture/video/example.mp4";
+    let mut fm = format::load_video_from_file(path).unwrap();
+    assert!(!fm.index_loaded());
+    let index = fm.build_index().unwrap();
+    assert_eq!(1, index.streams.len());
+    assert!(index.is_valid_for(&fm));
+
+    let mut fm = Format::builder().index(&index).open_file(path).unwrap();
+    assert!(fm.index_loaded());
+    let vs = &mut fm.video_streams()[0];
+    assert_eq!(index.stream(vs.index()), vs.frame_index());
+    let pts = vs.frame_index().unwrap().pts(10);
+    assert_eq!(pts, vs.frame_at(10).unwrap().meta.pts);
+
+    let mut stale = index.clone();
+    stale.key.file_size += 1;
+    let fm = Format::builder().index(&stale).open_file(path).unwrap();
+    assert!(!fm.index_loaded());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_index_serde_round_trip() {
+    ffmpeg::init();
+    let path = "fixture/video/example.mp4";
+    let mut fm = format::load_video_from_file(path).unwrap();
+    let index = fm.build_index().unwrap();
+    let pts = index.streams[0].1.pts(10);
+
+    let json = serde_json::to_string(&index).unwrap();
+    let restored: ffmpeg::MediaIndex = serde_json::from_str(&json).unwrap();
+    assert_eq!(index, restored);
+
+    let mut fm = Format::builder().index(&restored).open_file(path).unwrap();
+    assert!(fm.index_loaded());
+    let vs = &mut fm.video_streams()[0];
+    assert_eq!(pts, vs.frame_at(10).unwrap().meta.pts);
+}
+
+#[test]
+fn test_frames_at() {
+    ffmpeg::init();
+    let mut fm = format::load_video_from_file("fixture/video/example.mp4").unwrap();
+    let vs = &mut fm.video_streams()[0];
+    let times: Vec<Duration> = [1800, 100, 1200, 150, 100, 0]
+        .iter()
+        .map(|&ms| Duration::from_millis(ms))
+        .collect();
+
+    let mut expected = vec![];
+    for t in times.iter() {
+        vs.seek_by_time(t.as_secs_f32()).unwrap();
+        expected.push(vs.next_video_frame().unwrap().meta.pts);
+    }
+
+    let frames: Vec<_> = vs.frames_at(&times).map(|f| f.unwrap()).collect();
+    assert_eq!(times.len(), frames.len());
+    for (frame, pts) in frames.iter().zip(expected) {
+        assert_eq!(pts, frame.meta.pts);
+        assert_eq!(frames[0].buffer.len(), frame.buffer.len());
+    }
+
+    let mut frames = vs.frames_at(&[Duration::from_secs(3600)]);
+    assert_eq!(ErrorKind::Eof, frames.next().unwrap().unwrap_err().kind());
+    assert!(frames.next().is_none());
+}
+
+#[test]
+fn test_sample_at_fps() {
+    ffmpeg::init();
+    let mut fm = format::load_video_from_file("fixture/video/example.mp4").unwrap();
+    let vs = &mut fm.video_streams()[0];
+    let frame_time = 1.0 / vs.fps as f64;
+
+    let samples: Vec<_> = vs.sample_at_fps(5.0).unwrap().map(|s| s.unwrap()).collect();
+    assert!(samples.len() > 5);
+    for (i, sample) in samples.iter().enumerate() {
+        assert_eq!(i as u64, sample.tick);
+        assert!((sample.time - i as f64 / 5.0).abs() < 1e-9);
+        assert!((sample.source_time - sample.time).abs() <= frame_time / 2.0 + 1e-6);
+        assert_eq!((640, 360), (sample.frame.width, sample.frame.height));
+        assert_eq!(640 * 360 * 3, sample.frame.buffer.len());
+    }
+
+    vs.seek_by_time(0.0).unwrap();
+    let samples = vs.sample_at_fps_with(5.0, SampleMode::AtOrBefore).unwrap();
+    for sample in samples.take(10) {
+        let sample = sample.unwrap();
+        assert!(sample.source_time <= sample.time + 1e-6);
+        assert!(sample.time - sample.source_time < frame_time + 1e-6);
+    }
+
+    let err = vs.sample_at_fps(0.0).err().unwrap();
+    assert_eq!("invalid sample rate: Invalid argument", err.description());
+}
+
+#[test]
+fn test_sample_at_fps_mid_stream() {
+    ffmpeg::init();
+    let mut blob = vec![];
+    for i in 0..10 {
+        blob.extend(ppm(32, 16, i * 20));
+    }
+    for &(mode, tick, n) in [(SampleMode::Nearest, 0, 3), (SampleMode::AtOrBefore, 1, 6)].iter() {
+        let mut fm = Format::builder()
+            .input_format("ppm_pipe")
+            .open_blob(blob.clone())
+            .unwrap();
+        let vs = &mut fm.video_streams()[0];
+        // at the default 25 fps the first sampled frame is 0.12s in, between ticks at 4 fps
+        for _ in 0..3 {
+            vs.next_video_frame().unwrap();
+        }
+        let mut samples = vs.sample_at_fps_with(4.0, mode).unwrap();
+        let sample = samples.next().unwrap().unwrap();
+        assert_eq!(tick, sample.tick);
+        assert!((sample.source_time - n as f64 / 25.0).abs() < 1e-9);
+        assert_eq!(n * 20, sample.frame.buffer[0]);
+    }
+}
+
 #[test]
 fn test_decode_audio() {
     ffmpeg::init();